@@ -5,6 +5,7 @@ use std::{env, path::PathBuf, process::Command};
 fn main() {
     set_rerun();
     set_commit_info();
+    set_wasm_bindgen_version();
     if option_env!("CFG_RELEASE").is_none() {
         println!("cargo:rustc-env=POKE_RA_DEVS=1");
     }
@@ -48,3 +49,29 @@ fn set_commit_info() {
     println!("cargo:rustc-env=RA_COMMIT_SHORT_HASH={}", next());
     println!("cargo:rustc-env=RA_COMMIT_DATE={}", next())
 }
+
+/// Exposes the `wasm-bindgen` version this CLI links against as `WASM_BINDGEN_VERSION`
+fn set_wasm_bindgen_version() {
+    let manifest_dir = PathBuf::from(
+        env::var("CARGO_MANIFEST_DIR").expect("`CARGO_MANIFEST_DIR` is always set by cargo."),
+    );
+    let lockfile = manifest_dir.join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lockfile.display());
+
+    let Ok(content) = std::fs::read_to_string(lockfile) else {
+        return;
+    };
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        if line.trim() == "name = \"wasm-bindgen-cli-support\"" {
+            if let Some(version) = lines
+                .next()
+                .and_then(|l| l.trim().strip_prefix("version = \""))
+                .and_then(|l| l.strip_suffix('"'))
+            {
+                println!("cargo:rustc-env=WASM_BINDGEN_VERSION={}", version);
+            }
+            return;
+        }
+    }
+}
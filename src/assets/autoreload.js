@@ -4,22 +4,148 @@
 (function () {
   var protocol = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
   var url = protocol + '//' + window.location.host + '/_dioxus/ws';
-  var poll_interval = 8080;
-  var reload_upon_connect = () => {
-      window.setTimeout(
-          () => {
-              var ws = new WebSocket(url);
-              ws.onopen = () => window.location.reload();
-              ws.onclose = reload_upon_connect;
-          },
-          poll_interval);
-  };
+  // the version of the dev server protocol this script understands
+  var protocol_version = 2;
+  // reconnect delays in milliseconds, doubled after every failed attempt
+  var min_retry_delay = 500;
+  var max_retry_delay = 10000;
+  // the server pings every 10 seconds, so a silent connection is dead
+  var heartbeat_timeout = 25000;
+
+  // how many console messages are kept while disconnected
+  var max_pending = 100;
+
+  // the build this page was generated for, a page served before it may not be up to date
+  var build_id = document.currentScript && document.currentScript.dataset.buildId
+      ? Number(document.currentScript.dataset.buildId)
+      : null;
+  // the hot reload client of the app can't tell the server which build it runs, so its url gets
+  // the build id of the page, and templates of a newer build are never applied to an old page
+  if (build_id !== null) {
+      var NativeWebSocket = window.WebSocket;
+      window.WebSocket = class extends NativeWebSocket {
+          constructor(target, protocols) {
+              var target_url = new URL(target, window.location.href);
+              if (target_url.pathname == "/_dioxus/hot_reload") {
+                  target_url.searchParams.set("build_id", build_id);
+                  target = target_url.toString();
+              }
+              super(target, protocols);
+          }
+      };
+  }
+  var retry_delay = min_retry_delay;
+  var socket = null;
+  var pending = [];
 
-  var ws = new WebSocket(url);
-  ws.onmessage = (ev) => {
-      if (ev.data == "reload") {
-          window.location.reload();
+  // forward the console to the terminal running `dioxus serve`
+  var send_to_cli = (message) => {
+      if (socket !== null && socket.readyState == WebSocket.OPEN) {
+          socket.send(JSON.stringify(message));
+      } else if (pending.length < max_pending) {
+          pending.push(message);
       }
   };
-  ws.onclose = reload_upon_connect;
-})()
\ No newline at end of file
+  var format_arg = (arg) => {
+      if (typeof arg == "string") {
+          return arg;
+      }
+      if (arg instanceof Error) {
+          return arg.stack || String(arg);
+      }
+      try {
+          return JSON.stringify(arg);
+      } catch (_) {
+          return String(arg);
+      }
+  };
+  ["log", "info", "warn", "error", "debug"].forEach((level) => {
+      var original = console[level];
+      console[level] = function () {
+          original.apply(console, arguments);
+          // panics from `console_error_panic_hook` arrive here too
+          var message = Array.prototype.map.call(arguments, format_arg).join(" ");
+          send_to_cli({ type: "log", level: level, message: message });
+      };
+  });
+  window.addEventListener("error", (ev) => {
+      send_to_cli({
+          type: "error",
+          message: ev.message,
+          stack: ev.error && ev.error.stack ? ev.error.stack : null,
+      });
+  });
+  window.addEventListener("unhandledrejection", (ev) => {
+      send_to_cli({
+          type: "error",
+          message: "(in promise) " + format_arg(ev.reason),
+          stack: ev.reason && ev.reason.stack ? ev.reason.stack : null,
+      });
+  });
+
+  var connect = () => {
+      var ws = new WebSocket(url);
+      socket = ws;
+      var heartbeat = null;
+      var reset_heartbeat = () => {
+          window.clearTimeout(heartbeat);
+          heartbeat = window.setTimeout(() => ws.close(), heartbeat_timeout);
+      };
+
+      ws.onopen = () => {
+          retry_delay = min_retry_delay;
+          reset_heartbeat();
+          pending.splice(0).forEach(send_to_cli);
+      };
+      ws.onmessage = (ev) => {
+          reset_heartbeat();
+          var message = JSON.parse(ev.data);
+          switch (message.type) {
+              case "hello":
+                  if (message.version != protocol_version) {
+                      console.warn("[dioxus] dev server protocol version " + message.version + " is not supported");
+                  }
+                  // the app was rebuilt since this page was generated, or while we were disconnected
+                  if (build_id !== null && build_id != message.build_id) {
+                      window.location.reload();
+                  }
+                  build_id = message.build_id;
+                  break;
+              case "reload":
+                  window.location.reload();
+                  break;
+              case "update_css":
+                  // swap the changed stylesheets with a cache busting query instead of reloading the page
+                  document.querySelectorAll('link[rel="stylesheet"]').forEach((link) => {
+                      var href = new URL(link.href);
+                      var changed = message.hrefs.some((path) => href.pathname.endsWith("/" + path));
+                      if (changed) {
+                          href.searchParams.set("dx_reload", Date.now());
+                          link.href = href.toString();
+                      }
+                  });
+                  break;
+              case "update_assets":
+                  // refresh the elements showing the changed files instead of reloading the page
+                  document.querySelectorAll("img[src], source[src], video[src], audio[src], link[href]").forEach((element) => {
+                      var attribute = element.hasAttribute("src") ? "src" : "href";
+                      var url = new URL(element.getAttribute(attribute), window.location.href);
+                      var changed = message.hrefs.some((path) => url.pathname.endsWith("/" + path));
+                      if (changed) {
+                          url.searchParams.set("dx_reload", Date.now());
+                          element.setAttribute(attribute, url.toString());
+                      }
+                  });
+                  break;
+              // `ping` only keeps the connection alive
+          }
+      };
+      ws.onclose = () => {
+          window.clearTimeout(heartbeat);
+          window.setTimeout(connect, retry_delay);
+          retry_delay = Math.min(retry_delay * 2, max_retry_delay);
+      };
+  };
+
+  connect();
+})()
@@ -0,0 +1,170 @@
+-- Helpers for plugin tests, loaded before the plugin by `dioxus plugin test`.
+-- `command` and `network` are replaced by mocks, so tests never run programs or touch the network.
+
+local command_mocks = {}
+local url_mocks = {}
+
+test = {
+    -- every `command.exec` call, as the list of its arguments
+    commands = {},
+    -- every url passed to `network.download_file` and `network.request`
+    requests = {},
+}
+
+-- extra arguments of the hooks, on top of `test.fixture`
+local hook_fixtures = {
+    ["build.after_cargo"] = { wasm_files = {} },
+    ["build.after_bindgen"] = { out_name = "app", js_file = "app.js", wasm_file = "app_bg.wasm" },
+    ["build.after_wasm_opt"] = { out_name = "app", wasm_file = "app_bg.wasm" },
+    ["build.transform_asset"] = { path = "style.css", file = "style.css", content = "body {}" },
+    ["build.transform_html"] = {
+        html = "<html><head></head><body><div id=\"main\"></div></body></html>",
+        serve = false,
+    },
+    ["serve.on_rebuild"] = { timestamp = 0, changed_files = {} },
+}
+
+local function fail(message, level)
+    error(message, level + 1)
+end
+
+local function equal(left, right)
+    if type(left) ~= "table" or type(right) ~= "table" then
+        return left == right
+    end
+    for key, value in pairs(left) do
+        if not equal(value, right[key]) then
+            return false
+        end
+    end
+    for key in pairs(right) do
+        if left[key] == nil then
+            return false
+        end
+    end
+    return true
+end
+
+local function show(value)
+    if type(value) == "string" then
+        return string.format("%q", value)
+    elseif type(value) ~= "table" then
+        return tostring(value)
+    end
+    local items = {}
+    for key, item in pairs(value) do
+        table.insert(items, tostring(key) .. " = " .. show(item))
+    end
+    table.sort(items)
+    return "{ " .. table.concat(items, ", ") .. " }"
+end
+
+function test.assert(value, message)
+    if not value then
+        fail(message or "assertion failed", 2)
+    end
+end
+
+function test.assert_eq(left, right, message)
+    if not equal(left, right) then
+        local prefix = message and (message .. "\n") or ""
+        fail(prefix .. "assertion failed: `left == right`\n  left: " .. show(left) .. "\n right: " .. show(right), 2)
+    end
+end
+
+function test.assert_error(func, pattern)
+    local ok, err = pcall(func)
+    if ok then
+        fail("expected an error", 2)
+    end
+    if pattern and not string.find(tostring(err), pattern) then
+        fail("error `" .. tostring(err) .. "` doesn't match `" .. pattern .. "`", 2)
+    end
+end
+
+-- `output` has the `code`, `stdout` and `stderr` returned by `command.exec` for `program`
+function test.mock_command(program, output)
+    command_mocks[program] = output
+end
+
+-- `response` has the `status`, `headers` and `body` returned for `url`
+function test.mock_url(url, response)
+    url_mocks[url] = response
+end
+
+-- the hook at `path` in the manifest, like `build.on_start`
+function test.find_hook(path)
+    local hook = manager
+    for key in string.gmatch(path, "[^.]+") do
+        if type(hook) ~= "table" then
+            return nil
+        end
+        hook = hook[key]
+    end
+    if type(hook) == "function" then
+        return hook
+    end
+end
+
+-- call the hook at `path` with the fixture arguments, overridden by `args`
+function test.call_hook(path, args)
+    local hook = test.find_hook(path)
+    if hook == nil then
+        fail("the plugin has no `" .. path .. "` hook", 2)
+    end
+    local merged = {}
+    for _, values in ipairs({ test.fixture or {}, hook_fixtures[path] or {}, args or {} }) do
+        for key, value in pairs(values) do
+            merged[key] = value
+        end
+    end
+    return hook(merged)
+end
+
+-- like the real `exec`, only piped streams are captured, and streams are piped by default
+local function captured(stdio, text)
+    stdio = string.lower(stdio or "piped")
+    if stdio ~= "piped" and stdio ~= "inherit" and stdio ~= "null" then
+        error("expected \"inherit\", \"piped\" or \"null\", got \"" .. stdio .. "\"", 3)
+    end
+    if stdio == "piped" then
+        return text or ""
+    end
+    return ""
+end
+
+plugin_lib.command = {
+    exec = function (cmd, stdout, stderr)
+        table.insert(test.commands, cmd)
+        local output = command_mocks[cmd[1]] or {}
+        local code = output.code or 0
+        return {
+            code = code,
+            success = code == 0,
+            stdout = captured(stdout, output.stdout),
+            stderr = captured(stderr, output.stderr),
+        }
+    end,
+}
+
+local function mocked_response(url)
+    table.insert(test.requests, url)
+    return url_mocks[url]
+end
+
+plugin_lib.network = {
+    download_file = function (url, path)
+        local response = mocked_response(url)
+        if response == nil or (response.status or 200) >= 400 then
+            return false
+        end
+        return plugin_lib.fs.file_set_content(path, response.body or "")
+    end,
+    request = function (options)
+        local response = mocked_response(options.url)
+        if response == nil then
+            error("no mock for `" .. options.url .. "`, add one with `test.mock_url`", 2)
+        end
+        return { status = response.status or 200, headers = response.headers or {}, body = response.body or "" }
+    end,
+}
@@ -0,0 +1,64 @@
+// Dioxus-CLI
+// https://github.com/DioxusLabs/cli
+
+const CACHE_NAME = "{cache_name}";
+const PRECACHE = {precache};
+const INDEX = "{index}";
+const STRATEGY = "{strategy}";
+
+self.addEventListener("install", (event) => {
+  event.waitUntil(
+    caches
+      .open(CACHE_NAME)
+      .then((cache) => cache.addAll(PRECACHE))
+      .then(() => self.skipWaiting())
+  );
+});
+
+self.addEventListener("activate", (event) => {
+  // drop the caches of older builds
+  event.waitUntil(
+    caches
+      .keys()
+      .then((keys) =>
+        Promise.all(
+          keys.filter((key) => key !== CACHE_NAME).map((key) => caches.delete(key))
+        )
+      )
+      .then(() => self.clients.claim())
+  );
+});
+
+var from_cache = (request) =>
+  caches.match(request).then((cached) => {
+    if (cached) {
+      return cached;
+    }
+    // routes handled by the app are served by the index page
+    if (request.mode === "navigate") {
+      return caches.match(INDEX);
+    }
+    return undefined;
+  });
+
+self.addEventListener("fetch", (event) => {
+  if (event.request.method !== "GET") {
+    return;
+  }
+
+  if (STRATEGY === "network_first") {
+    event.respondWith(
+      fetch(event.request)
+        .then((response) => {
+          var copy = response.clone();
+          caches.open(CACHE_NAME).then((cache) => cache.put(event.request, copy));
+          return response;
+        })
+        .catch(() => from_cache(event.request))
+    );
+  } else {
+    event.respondWith(
+      from_cache(event.request).then((cached) => cached || fetch(event.request))
+    );
+  }
+});
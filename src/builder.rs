@@ -1,6 +1,7 @@
 use crate::{
-    config::{CrateConfig, ExecutableType},
+    config::{CrateConfig, ExecutableType, WebEntryRole},
     error::{Error, Result},
+    plugin::PluginManager,
     tools::Tool,
     DioxusConfig,
 };
@@ -11,12 +12,15 @@ use std::{
     fs::{copy, create_dir_all, File},
     io::Read,
     panic,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::Command,
     time::Duration,
 };
 use wasm_bindgen_cli_support::Bindgen;
 
+/// The `wasm-bindgen` version this CLI was compiled with, read from our own `Cargo.lock` by `build.rs`
+const WASM_BINDGEN_VERSION: Option<&str> = option_env!("WASM_BINDGEN_VERSION");
+
 #[derive(Serialize, Debug, Clone)]
 pub struct BuildResult {
     pub warnings: Vec<Diagnostic>,
@@ -35,12 +39,38 @@ pub fn build(config: &CrateConfig, quiet: bool) -> Result<BuildResult> {
         out_dir,
         crate_dir,
         target_dir,
-        asset_dir,
         executable,
         dioxus_config,
         ..
     } = config;
 
+    // make sure the bindgen schema of the app matches the one we link against
+    check_wasm_bindgen_version(config)?;
+
+    let app_name = &dioxus_config.application.name;
+    let entries = dioxus_config.web.entries();
+    if entries
+        .iter()
+        .filter(|entry| entry.role == WebEntryRole::Main)
+        .count()
+        > 1
+    {
+        return Err(Error::BuildFailed(
+            "Only one `[[web.entry]]` can have the `main` role.".into(),
+        ));
+    }
+    // entries with the same output name would overwrite each other's js and wasm
+    let mut out_names: Vec<String> = vec![];
+    for entry in &entries {
+        let out_name = entry.out_name(app_name);
+        if out_names.contains(&out_name) {
+            return Err(Error::BuildFailed(format!(
+                "Several `[[web.entry]]` would be built to `{out_name}.js`, set an `out_name`."
+            )));
+        }
+        out_names.push(out_name);
+    }
+
     // start to build the assets
     let ignore_files = build_assets(config)?;
 
@@ -83,11 +113,15 @@ pub fn build(config: &CrateConfig, quiet: bool) -> Result<BuildResult> {
         cmd
     };
 
-    let cmd = match executable {
-        ExecutableType::Binary(name) => cmd.arg("--bin").arg(name),
-        ExecutableType::Lib(name) => cmd.arg("--lib").arg(name),
-        ExecutableType::Example(name) => cmd.arg("--example").arg(name),
-    };
+    // all entries are compiled by a single cargo invocation
+    let mut cmd = cmd;
+    for entry in &entries {
+        cmd = match entry.executable(executable) {
+            ExecutableType::Binary(name) => cmd.arg("--bin").arg(name),
+            ExecutableType::Lib(name) => cmd.arg("--lib").arg(name),
+            ExecutableType::Example(name) => cmd.arg("--example").arg(name),
+        };
+    }
 
     let warning_messages = prettier_build(cmd)?;
 
@@ -99,86 +133,202 @@ pub fn build(config: &CrateConfig, quiet: bool) -> Result<BuildResult> {
         false => "debug",
     };
 
-    let input_path = match executable {
-        ExecutableType::Binary(name) | ExecutableType::Lib(name) => target_dir
-            .join(format!("wasm32-unknown-unknown/{}", release_type))
-            .join(format!("{}.wasm", name)),
+    let input_paths: Vec<PathBuf> = entries
+        .iter()
+        .map(|entry| match entry.executable(executable) {
+            ExecutableType::Binary(name) | ExecutableType::Lib(name) => target_dir
+                .join(format!("wasm32-unknown-unknown/{}", release_type))
+                .join(format!("{}.wasm", name)),
+
+            ExecutableType::Example(name) => target_dir
+                .join(format!("wasm32-unknown-unknown/{}/examples", release_type))
+                .join(format!("{}.wasm", name)),
+        })
+        .collect();
+    PluginManager::after_cargo(config, &input_paths)?;
+
+    for (entry, input_path) in entries.iter().zip(input_paths) {
+        let out_name = entry.out_name(app_name);
+
+        let bindgen_config = &dioxus_config.web.bindgen;
+        let release = config.release;
+        let bindgen_outdir = &bindgen_outdir;
+        let bindgen_out_name = &out_name;
+        let bindgen_result = panic::catch_unwind(move || {
+            // [3] Bindgen the final binary for use easy linking
+            let mut bindgen_builder = Bindgen::new();
+
+            bindgen_builder
+                .input_path(input_path)
+                .web(true)
+                .unwrap()
+                .debug(bindgen_config.debug(release))
+                .demangle(bindgen_config.demangle())
+                .keep_debug(bindgen_config.keep_debug(release))
+                .remove_name_section(release && !bindgen_config.keep_debug(release))
+                .remove_producers_section(release)
+                .reference_types(bindgen_config.reference_types())
+                .weak_refs(bindgen_config.weak_refs())
+                .typescript(bindgen_config.typescript())
+                .split_linked_modules(bindgen_config.split_linked_modules())
+                .out_name(bindgen_out_name)
+                .generate(bindgen_outdir)
+                .unwrap();
+        });
+        if bindgen_result.is_err() {
+            return Err(Error::BuildFailed(format!(
+                "Bindgen build failed! \nThis is probably due to the Bindgen version, dioxus-cli using `{}` Bindgen crate.",
+                WASM_BINDGEN_VERSION.unwrap_or("unknown")
+            )));
+        }
 
-        ExecutableType::Example(name) => target_dir
-            .join(format!("wasm32-unknown-unknown/{}/examples", release_type))
-            .join(format!("{}.wasm", name)),
-    };
+        let wasm_file = bindgen_outdir.join(format!("{}_bg.wasm", out_name));
+        PluginManager::after_bindgen(
+            config,
+            &out_name,
+            &bindgen_outdir.join(format!("{}.js", out_name)),
+            &wasm_file,
+        )?;
+
+        // workers can't be started from the wasm-bindgen glue directly, they need a module that runs `init`
+        if let Some(bootstrap) = entry.bootstrap_path(app_name) {
+            let base_path = dioxus_config.web.app.base_path.as_deref().unwrap_or(".");
+            std::fs::write(
+                out_dir.join(bootstrap),
+                format!(
+                    r#"import init from "/{base_path}/assets/dioxus/{out_name}.js";
+init("/{base_path}/assets/dioxus/{out_name}_bg.wasm");
+"#
+                ),
+            )?;
+        }
 
-    let bindgen_result = panic::catch_unwind(move || {
-        // [3] Bindgen the final binary for use easy linking
-        let mut bindgen_builder = Bindgen::new();
+        // check binaryen:wasm-opt tool
+        wasm_opt(config, &wasm_file)?;
+        PluginManager::after_wasm_opt(config, &out_name, &wasm_file)?;
+    }
 
-        bindgen_builder
-            .input_path(input_path)
-            .web(true)
-            .unwrap()
-            .debug(true)
-            .demangle(true)
-            .keep_debug(true)
-            .remove_name_section(false)
-            .remove_producers_section(false)
-            .out_name(&dioxus_config.application.name)
-            .generate(&bindgen_outdir)
-            .unwrap();
-    });
-    if bindgen_result.is_err() {
-        return Err(Error::BuildFailed("Bindgen build failed! \nThis is probably due to the Bindgen version, dioxus-cli using `0.2.81` Bindgen crate.".to_string()));
-    }
-
-    // check binaryen:wasm-opt tool
-    let dioxus_tools = dioxus_config.application.tools.clone().unwrap_or_default();
-    if dioxus_tools.contains_key("binaryen") {
-        let info = dioxus_tools.get("binaryen").unwrap();
-        let binaryen = crate::tools::Tool::Binaryen;
+    // [5][OPTIONAL] If tailwind is enabled and installed we run it to generate the CSS
+    build_tailwind(config)?;
 
-        if binaryen.is_installed() {
-            if let Some(sub) = info.as_table() {
-                if sub.contains_key("wasm_opt")
-                    && sub.get("wasm_opt").unwrap().as_bool().unwrap_or(false)
-                {
-                    log::info!("Optimizing WASM size with wasm-opt...");
-                    let target_file = out_dir
-                        .join("assets")
-                        .join("dioxus")
-                        .join(format!("{}_bg.wasm", dioxus_config.application.name));
-                    if target_file.is_file() {
-                        let mut args = vec![
-                            target_file.to_str().unwrap(),
-                            "-o",
-                            target_file.to_str().unwrap(),
-                        ];
-                        if config.release == true {
-                            args.push("-Oz");
-                        }
-                        binaryen.call("wasm-opt", args)?;
-                    }
-                }
+    copy_assets(config, &ignore_files)?;
+
+    let t_end = std::time::Instant::now();
+    Ok(BuildResult {
+        warnings: warning_messages,
+        elapsed_time: (t_end - t_start).as_millis(),
+    })
+}
+
+/// Copy the `changed` files of the asset dir to the output dir, removing the copies of deleted ones
+///
+/// Returns the urls of the changed files, relative to the output dir.
+pub fn copy_changed_assets(config: &CrateConfig, changed: &[PathBuf]) -> Result<Vec<String>> {
+    let mut hrefs = vec![];
+    for path in changed {
+        let Ok(relative) = path.strip_prefix(&config.asset_dir) else {
+            continue;
+        };
+        let target = config.out_dir.join(relative);
+        if path.is_file() {
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
             }
+            std::fs::copy(path, &target)?;
+            transform_asset(config, relative, &target)?;
+        } else if !path.exists() && target.is_file() {
+            std::fs::remove_file(&target)?;
         } else {
-            log::warn!(
-                "Binaryen tool not found, you can use `dioxus tool add binaryen` to install it."
-            );
+            continue;
         }
+        hrefs.push(url_path(relative));
     }
+    Ok(hrefs)
+}
+
+/// Recompile only the stylesheets affected by `changed` files of the asset dir
+///
+/// Returns the urls of the updated stylesheets, relative to the output dir.
+pub fn rebuild_styles(config: &CrateConfig, changed: &[PathBuf]) -> Result<Vec<String>> {
+    let mut hrefs = vec![];
+    let tailwind_input = tailwind_input(config);
+
+    for path in changed {
+        if Some(path) == tailwind_input.as_ref() {
+            build_tailwind(config)?;
+            hrefs.push("tailwind.css".to_string());
+            continue;
+        }
+
+        let Ok(relative) = path.strip_prefix(&config.asset_dir) else {
+            continue;
+        };
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("css") => {
+                let target = config.out_dir.join(relative);
+                if let Some(parent) = target.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::copy(path, target)?;
+                hrefs.push(url_path(relative));
+            }
+            Some("scss" | "sass") => {
+                // partials are only used through imports, so every sass input may depend on them
+                let is_partial = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .map_or(false, |n| n.starts_with('_'));
+                let compiled = build_sass(config, (!is_partial).then_some(path.as_path()))?;
+                for source in compiled {
+                    let relative = source.strip_prefix(&config.asset_dir).unwrap();
+                    hrefs.push(url_path(&relative.with_extension("css")));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    hrefs.sort();
+    hrefs.dedup();
+    Ok(hrefs)
+}
+
+fn url_path(relative: &Path) -> String {
+    relative.to_str().unwrap().replace('\\', "/")
+}
+
+/// The css file tailwind reads, if tailwind is enabled
+///
+/// Like the paths reported by the file watcher, it isn't canonicalized, so the two compare equal.
+pub fn tailwind_input(config: &CrateConfig) -> Option<PathBuf> {
+    let tools = config.dioxus_config.application.tools.as_ref()?;
+    let input = tools
+        .get("tailwindcss")?
+        .as_table()?
+        .get("input")
+        .and_then(|val| val.as_str())
+        .unwrap_or("./public");
+    Some(config.crate_dir.join(input))
+}
+
+// build the tailwind bundle if tailwind is enabled and installed
+fn build_tailwind(config: &CrateConfig) -> Result<()> {
+    let dioxus_tools = config
+        .dioxus_config
+        .application
+        .tools
+        .clone()
+        .unwrap_or_default();
 
-    // [5][OPTIONAL] If tailwind is enabled and installed we run it to generate the CSS
     if dioxus_tools.contains_key("tailwindcss") {
         let info = dioxus_tools.get("tailwindcss").unwrap();
         let tailwind = crate::tools::Tool::Tailwind;
 
         if tailwind.is_installed() {
-            if let Some(sub) = info.as_table() {
+            if let (Some(sub), Some(input)) = (info.as_table(), tailwind_input(config)) {
                 log::info!("Building Tailwind bundle CSS file...");
 
-                let input_path = match sub.get("input") {
-                    Some(val) => val.as_str().unwrap(),
-                    None => "./public",
-                };
+                let input_path = input.to_str().unwrap();
                 let config_path = match sub.get("config") {
                     Some(val) => val.as_str().unwrap(),
                     None => "./src/tailwind.config.js",
@@ -204,7 +354,11 @@ pub fn build(config: &CrateConfig, quiet: bool) -> Result<BuildResult> {
             );
         }
     }
+    Ok(())
+}
 
+// copy every file of the asset dir to the output dir, except those in `ignore_files`
+fn copy_assets(config: &CrateConfig, ignore_files: &[PathBuf]) -> Result<()> {
     // this code will copy all public file to the output dir
     let copy_options = fs_extra::dir::CopyOptions {
         overwrite: true,
@@ -214,19 +368,19 @@ pub fn build(config: &CrateConfig, quiet: bool) -> Result<BuildResult> {
         content_only: false,
         depth: 0,
     };
-    if asset_dir.is_dir() {
-        for entry in std::fs::read_dir(&asset_dir)? {
+    if config.asset_dir.is_dir() {
+        for entry in std::fs::read_dir(&config.asset_dir)? {
             let path = entry?.path();
             if path.is_file() {
-                std::fs::copy(&path, out_dir.join(path.file_name().unwrap()))?;
+                std::fs::copy(&path, config.out_dir.join(path.file_name().unwrap()))?;
             } else {
-                match fs_extra::dir::copy(&path, out_dir, &copy_options) {
+                match fs_extra::dir::copy(&path, &config.out_dir, &copy_options) {
                     Ok(_) => {}
                     Err(_e) => {
                         log::warn!("Error copying dir: {}", _e);
                     }
                 }
-                for ignore in &ignore_files {
+                for ignore in ignore_files {
                     let ignore = ignore.strip_prefix(&config.asset_dir).unwrap();
                     let ignore = config.out_dir.join(ignore);
                     if ignore.is_file() {
@@ -235,13 +389,100 @@ pub fn build(config: &CrateConfig, quiet: bool) -> Result<BuildResult> {
                 }
             }
         }
+
+        if PluginManager::has_hook(|info| info.build.transform_asset) {
+            for entry in walkdir::WalkDir::new(&config.asset_dir)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+            {
+                if ignore_files.iter().any(|ignore| ignore == entry.path()) {
+                    continue;
+                }
+                let relative = entry.path().strip_prefix(&config.asset_dir).unwrap();
+                transform_asset(config, relative, &config.out_dir.join(relative))?;
+            }
+        }
     }
+    Ok(())
+}
 
-    let t_end = std::time::Instant::now();
-    Ok(BuildResult {
-        warnings: warning_messages,
-        elapsed_time: (t_end - t_start).as_millis(),
-    })
+// let plugins transform the copy of an asset in the output dir
+fn transform_asset(config: &CrateConfig, relative: &Path, target: &Path) -> Result<()> {
+    if !PluginManager::has_hook(|info| info.build.transform_asset) {
+        return Ok(());
+    }
+    let content = std::fs::read(target)?;
+    let transformed =
+        PluginManager::transform_asset(config, &url_path(relative), target, content.clone())?;
+    if transformed != content {
+        std::fs::write(target, transformed)?;
+    }
+    Ok(())
+}
+
+// run `wasm-opt` over a bindgen output file if binaryen is enabled and installed
+fn wasm_opt(config: &CrateConfig, target_file: &Path) -> Result<()> {
+    let dioxus_tools = config
+        .dioxus_config
+        .application
+        .tools
+        .clone()
+        .unwrap_or_default();
+    if dioxus_tools.contains_key("binaryen") {
+        let info = dioxus_tools.get("binaryen").unwrap();
+        let binaryen = crate::tools::Tool::Binaryen;
+
+        if binaryen.is_installed() {
+            if let Some(sub) = info.as_table() {
+                if sub.contains_key("wasm_opt")
+                    && sub.get("wasm_opt").unwrap().as_bool().unwrap_or(false)
+                {
+                    log::info!("Optimizing WASM size with wasm-opt...");
+                    if target_file.is_file() {
+                        let mut args = vec![
+                            target_file.to_str().unwrap(),
+                            "-o",
+                            target_file.to_str().unwrap(),
+                        ];
+                        if config.release == true {
+                            args.push("-Oz");
+                        }
+                        binaryen.call("wasm-opt", args)?;
+                    }
+                }
+            }
+        } else {
+            log::warn!(
+                "Binaryen tool not found, you can use `dioxus tool add binaryen` to install it."
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Compare the `wasm-bindgen` version locked by the app against the one bundled in the CLI
+///
+/// The bindgen schema has to match exactly, otherwise `Bindgen::generate` panics deep inside the
+/// wasm parser. Catching that here lets us tell the user which versions disagree.
+pub fn check_wasm_bindgen_version(config: &CrateConfig) -> Result<()> {
+    let Some(cli_version) = WASM_BINDGEN_VERSION else {
+        return Ok(());
+    };
+    let Some(app_version) =
+        crate::cargo::locked_package_version(&config.workspace_dir, "wasm-bindgen")
+    else {
+        return Ok(());
+    };
+
+    if app_version != cli_version {
+        return Err(Error::BuildFailed(format!(
+            "wasm-bindgen version mismatch: your project uses `{app_version}` but dioxus-cli was built with `{cli_version}`.\n\
+             Run `cargo update -p wasm-bindgen --precise {cli_version}` or install a dioxus-cli built against `{app_version}`."
+        )));
+    }
+
+    Ok(())
 }
 
 pub fn build_desktop(config: &CrateConfig, _is_serve: bool) -> Result<()> {
@@ -249,11 +490,16 @@ pub fn build_desktop(config: &CrateConfig, _is_serve: bool) -> Result<()> {
 
     let ignore_files = build_assets(config)?;
 
+    // cargo can't draw over the interactive UI, its errors are reported instead
+    let output_stream = || match crate::tui::is_active() {
+        true => std::process::Stdio::piped(),
+        false => std::process::Stdio::inherit(),
+    };
     let mut cmd = Command::new("cargo");
     cmd.current_dir(&config.crate_dir)
         .arg("build")
-        .stdout(std::process::Stdio::inherit())
-        .stderr(std::process::Stdio::inherit());
+        .stdout(output_stream())
+        .stderr(output_stream());
 
     if config.release {
         cmd.arg("--release");
@@ -283,7 +529,11 @@ pub fn build_desktop(config: &CrateConfig, _is_serve: bool) -> Result<()> {
     let output = cmd.output()?;
 
     if !output.status.success() {
-        return Err(Error::BuildFailed("Program build failed.".into()));
+        let message = format!(
+            "Program build failed.\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(Error::BuildFailed(message.trim_end().to_string()));
     }
 
     if output.status.success() {
@@ -321,37 +571,7 @@ pub fn build_desktop(config: &CrateConfig, _is_serve: bool) -> Result<()> {
         copy(res_path, &config.out_dir.join(target_file))?;
 
         // this code will copy all public file to the output dir
-        if config.asset_dir.is_dir() {
-            let copy_options = fs_extra::dir::CopyOptions {
-                overwrite: true,
-                skip_exist: false,
-                buffer_size: 64000,
-                copy_inside: false,
-                content_only: false,
-                depth: 0,
-            };
-
-            for entry in std::fs::read_dir(&config.asset_dir)? {
-                let path = entry?.path();
-                if path.is_file() {
-                    std::fs::copy(&path, &config.out_dir.join(path.file_name().unwrap()))?;
-                } else {
-                    match fs_extra::dir::copy(&path, &config.out_dir, &copy_options) {
-                        Ok(_) => {}
-                        Err(e) => {
-                            log::warn!("Error copying dir: {}", e);
-                        }
-                    }
-                    for ignore in &ignore_files {
-                        let ignore = ignore.strip_prefix(&config.asset_dir).unwrap();
-                        let ignore = config.out_dir.join(ignore);
-                        if ignore.is_file() {
-                            std::fs::remove_file(ignore)?;
-                        }
-                    }
-                }
-            }
-        }
+        copy_assets(config, &ignore_files)?;
 
         log::info!(
             "🚩 Build completed: [./{}]",
@@ -368,6 +588,138 @@ pub fn build_desktop(config: &CrateConfig, _is_serve: bool) -> Result<()> {
     Ok(())
 }
 
+/// Build the wasm client and the server binary of a fullstack app
+///
+/// The server is compiled into its own target dir so both cargo invocations can run in parallel
+/// instead of waiting on each other's build directory lock.
+pub fn build_fullstack(config: &CrateConfig, quiet: bool) -> Result<(BuildResult, PathBuf)> {
+    let fullstack = config.dioxus_config.fullstack.clone().unwrap_or_default();
+    check_features(
+        config,
+        "fullstack.client_features",
+        &fullstack.client_features(),
+    )?;
+    check_features(
+        config,
+        "fullstack.server_features",
+        &fullstack.server_features(),
+    )?;
+
+    let client_config = fullstack_client_config(config);
+    std::thread::scope(|scope| {
+        let server = scope.spawn(|| build_server(config, quiet));
+        let client = build(&client_config, quiet);
+        let server = server
+            .join()
+            .map_err(|_| Error::BuildFailed("Server build panicked.".into()))?;
+        Ok((client?, server?))
+    })
+}
+
+/// Make sure the features named by `key` in Dioxus.toml exist in Cargo.toml
+///
+/// Cargo would reject them as well, but without saying where they came from.
+fn check_features(config: &CrateConfig, key: &str, features: &[String]) -> Result<()> {
+    let manifest = &config.manifest;
+    for feature in features {
+        let name = feature.strip_prefix("dep:").unwrap_or(feature);
+        let known = name.contains('/')
+            || manifest.features.contains_key(name)
+            || manifest
+                .dependencies
+                .get(name)
+                .map_or(false, |dep| dep.optional());
+        if !known {
+            return Err(Error::BuildFailed(format!(
+                "The feature `{feature}` of `{key}` in Dioxus.toml isn't defined in Cargo.toml."
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The config of the wasm client of a fullstack app, with the client features enabled
+pub fn fullstack_client_config(config: &CrateConfig) -> CrateConfig {
+    let fullstack = config.dioxus_config.fullstack.clone().unwrap_or_default();
+    let mut client_config = config.clone();
+    let mut client_features = config.features.clone().unwrap_or_default();
+    client_features.extend(fullstack.client_features());
+    if !client_features.is_empty() {
+        client_config.set_features(client_features);
+    }
+    client_config
+}
+
+/// Build the server binary of a fullstack app and return its path
+pub fn build_server(config: &CrateConfig, quiet: bool) -> Result<PathBuf> {
+    log::info!("🚅 Running build [Server] command...");
+
+    let fullstack = config.dioxus_config.fullstack.clone().unwrap_or_default();
+    let target_dir = config.target_dir.join("dioxus-server");
+
+    let mut cmd = Command::new("cargo");
+    cmd.current_dir(&config.crate_dir)
+        .arg("build")
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .stdout(std::process::Stdio::inherit())
+        .stderr(std::process::Stdio::inherit());
+
+    if config.release {
+        cmd.arg("--release");
+    }
+    if config.verbose {
+        cmd.arg("--verbose");
+    }
+    if quiet {
+        cmd.arg("--quiet");
+    }
+
+    if config.custom_profile.is_some() {
+        let custom_profile = config.custom_profile.as_ref().unwrap();
+        cmd.arg("--profile");
+        cmd.arg(custom_profile);
+    }
+
+    let mut features = config.features.clone().unwrap_or_default();
+    features.extend(fullstack.server_features());
+    if !features.is_empty() {
+        cmd.arg("--features");
+        cmd.arg(features.join(" "));
+    }
+
+    let executable = match &fullstack.server_bin {
+        Some(bin) => ExecutableType::Binary(bin.clone()),
+        None => config.executable.clone(),
+    };
+    match &executable {
+        ExecutableType::Binary(name) => cmd.arg("--bin").arg(name),
+        ExecutableType::Lib(name) => cmd.arg("--lib").arg(name),
+        ExecutableType::Example(name) => cmd.arg("--example").arg(name),
+    };
+
+    let output = cmd.output()?;
+    if !output.status.success() {
+        return Err(Error::BuildFailed("Server build failed.".into()));
+    }
+
+    let release_type = match config.release {
+        true => "release",
+        false => "debug",
+    };
+    let mut res_path = match &executable {
+        ExecutableType::Binary(name) | ExecutableType::Lib(name) => {
+            target_dir.join(release_type).join(name)
+        }
+        ExecutableType::Example(name) => target_dir.join(release_type).join("examples").join(name),
+    };
+    if cfg!(windows) {
+        res_path.set_extension("exe");
+    }
+
+    Ok(res_path)
+}
+
 fn prettier_build(cmd: subprocess::Exec) -> anyhow::Result<Vec<Diagnostic>> {
     let mut warning_messages: Vec<Diagnostic> = vec![];
 
@@ -379,6 +731,10 @@ fn prettier_build(cmd: subprocess::Exec) -> anyhow::Result<Vec<Diagnostic>> {
             .tick_chars("/|\\- "),
     );
     pb.set_message("💼 Waiting to start build the project...");
+    // the interactive UI shows the progress itself
+    if crate::tui::is_active() {
+        pb.set_draw_target(indicatif::ProgressDrawTarget::hidden());
+    }
 
     struct StopSpinOnDrop(ProgressBar);
 
@@ -413,6 +769,10 @@ fn prettier_build(cmd: subprocess::Exec) -> anyhow::Result<Vec<Diagnostic>> {
             Message::CompilerArtifact(artifact) => {
                 pb.set_message(format!("Compiling {} ", artifact.package_id));
                 pb.tick();
+                crate::tui::send(crate::tui::TuiEvent::BuildProgress(format!(
+                    "Compiling {}",
+                    artifact.target.name
+                )));
             }
             Message::BuildScriptExecuted(script) => {
                 let _package_id = script.package_id.to_string();
@@ -421,7 +781,8 @@ fn prettier_build(cmd: subprocess::Exec) -> anyhow::Result<Vec<Diagnostic>> {
                 if finished.success {
                     log::info!("👑 Build done.");
                 } else {
-                    std::process::exit(1);
+                    // `build --watch` keeps running after a failed build, so don't exit here
+                    return Err(anyhow::anyhow!("Cargo build failed."));
                 }
             }
             _ => (), // Unknown message
@@ -430,7 +791,12 @@ fn prettier_build(cmd: subprocess::Exec) -> anyhow::Result<Vec<Diagnostic>> {
     Ok(warning_messages)
 }
 
-pub fn gen_page(config: &DioxusConfig, serve: bool) -> String {
+/// Generate the `index.html` of the app
+///
+/// With a `build_id` the page is meant for the dev server: it gets the dev resources and the
+/// reload script, which compares the id with the one of the build the dev server is running.
+pub fn gen_page(config: &DioxusConfig, build_id: Option<u64>) -> String {
+    let serve = build_id.is_some();
     let crate_root = crate::cargo::crate_root().unwrap();
     let custom_html_file = crate_root.join("index.html");
     let mut html = if custom_html_file.is_file() {
@@ -474,6 +840,25 @@ pub fn gen_page(config: &DioxusConfig, serve: bool) -> String {
         style_str.push_str("<link rel=\"stylesheet\" href=\"tailwind.css\">\n");
     }
 
+    // the PWA service worker would cache stale files while developing, so it's only used on build
+    if !serve && config.web.pwa.is_some() {
+        let prefix = crate::pwa::url_prefix(config);
+        style_str.push_str(&format!(
+            "<link rel=\"manifest\" href=\"{prefix}{}\">\n",
+            crate::pwa::MANIFEST_FILE
+        ));
+        if let Some(theme_color) = config
+            .web
+            .pwa
+            .as_ref()
+            .and_then(|pwa| pwa.theme_color.as_ref())
+        {
+            style_str.push_str(&format!(
+                "<meta name=\"theme-color\" content=\"{theme_color}\">\n"
+            ));
+        }
+    }
+
     replace_or_insert_before("{style_include}", &style_str, "</head", &mut html);
 
     let mut script_str = String::new();
@@ -486,9 +871,9 @@ pub fn gen_page(config: &DioxusConfig, serve: bool) -> String {
 
     replace_or_insert_before("{script_include}", &script_str, "</body", &mut html);
 
-    if serve {
+    if let Some(build_id) = build_id {
         html += &format!(
-            "<script>{}</script>",
+            "<script data-build-id=\"{build_id}\">{}</script>",
             include_str!("./assets/autoreload.js")
         );
     }
@@ -497,13 +882,20 @@ pub fn gen_page(config: &DioxusConfig, serve: bool) -> String {
         Some(path) => path,
         None => ".",
     };
-    let app_name = &config.application.name;
+    let entries = config.web.entries();
+    let main_entry = entries
+        .iter()
+        .find(|entry| entry.role == WebEntryRole::Main);
+    let app_name = &match main_entry {
+        Some(entry) => entry.out_name(&config.application.name),
+        None => config.application.name.clone(),
+    };
     // Check if a script already exists
     if html.contains("{app_name}") && html.contains("{base_path}") {
         html = html.replace("{app_name}", app_name);
 
         html = html.replace("{base_path}", base_path);
-    } else {
+    } else if main_entry.is_some() {
         // If not, insert the script
         html = html.replace(
             "</body",
@@ -521,6 +913,42 @@ pub fn gen_page(config: &DioxusConfig, serve: bool) -> String {
         );
     }
 
+    // register the service worker entries, plain workers are started by the app itself
+    for entry in entries
+        .iter()
+        .filter(|entry| entry.role == WebEntryRole::ServiceWorker)
+    {
+        let bootstrap = entry.bootstrap_path(&config.application.name).unwrap();
+        let bootstrap = bootstrap.to_str().unwrap();
+        html = html.replace(
+            "</body",
+            &format!(
+                r#"<script>
+    if ("serviceWorker" in navigator) {{
+      navigator.serviceWorker.register("/{base_path}/{bootstrap}", {{ type: "module" }});
+    }}
+    </script>
+    </body"#
+            ),
+        );
+    }
+
+    if !serve && crate::pwa::uses_generated_service_worker(config) {
+        let prefix = crate::pwa::url_prefix(config);
+        html = html.replace(
+            "</body",
+            &format!(
+                r#"<script>
+    if ("serviceWorker" in navigator) {{
+      navigator.serviceWorker.register("{prefix}{}");
+    }}
+    </script>
+    </body"#,
+                crate::pwa::SERVICE_WORKER_FILE
+            ),
+        );
+    }
+
     let title = config
         .web
         .app
@@ -550,7 +978,13 @@ fn replace_or_insert_before(
 // like sass tool resources
 // this function will return a array which file don't need copy to out_dir.
 fn build_assets(config: &CrateConfig) -> Result<Vec<PathBuf>> {
+    build_sass(config, None)
+}
+
+// compile the configured sass inputs, or only `only` if it is one of them
+fn build_sass(config: &CrateConfig, only: Option<&Path>) -> Result<Vec<PathBuf>> {
     let mut result = vec![];
+    let selected = |path: &Path| only.map_or(true, |only| only == path);
 
     let dioxus_config = &config.dioxus_config;
     let dioxus_tools = dioxus_config.application.tools.clone().unwrap_or_default();
@@ -590,7 +1024,7 @@ fn build_assets(config: &CrateConfig) -> Result<Vec<PathBuf>> {
                                         continue;
                                     }
                                     let suffix = suffix.unwrap().to_str().unwrap();
-                                    if suffix == "scss" || suffix == "sass" {
+                                    if (suffix == "scss" || suffix == "sass") && selected(temp) {
                                         // if file suffix is `scss` / `sass` we need transform it.
                                         let out_file = format!(
                                             "{}.css",
@@ -634,7 +1068,7 @@ fn build_assets(config: &CrateConfig) -> Result<Vec<PathBuf>> {
                             .out_dir
                             .join(PathBuf::from(relative_path).parent().unwrap())
                             .join(out_file);
-                        if path.is_file() {
+                        if path.is_file() && selected(&path) {
                             let res = sass.call(
                                 "sass",
                                 vec![
@@ -668,7 +1102,7 @@ fn build_assets(config: &CrateConfig) -> Result<Vec<PathBuf>> {
                                 .out_dir
                                 .join(PathBuf::from(relative_path).parent().unwrap())
                                 .join(out_file);
-                            if path.is_file() {
+                            if path.is_file() && selected(&path) {
                                 let res = sass.call(
                                     "sass",
                                     vec![
@@ -723,3 +1157,53 @@ fn build_assets(config: &CrateConfig) -> Result<Vec<PathBuf>> {
 //         install_permitted,
 //     )?)
 // }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fullstack_config() -> CrateConfig {
+        let mut config = CrateConfig::for_test(Path::new("/app"));
+        config.manifest = cargo_toml::Manifest::from_str(
+            r#"
+            [package]
+            name = "app"
+            version = "0.1.0"
+
+            [features]
+            ssr = ["dep:axum"]
+
+            [dependencies]
+            axum = { version = "0.6", optional = true }
+            serde = "1"
+            "#,
+        )
+        .unwrap();
+        config
+    }
+
+    fn features(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn defined_features_pass() {
+        let config = fullstack_config();
+        let defined = features(&["ssr", "axum", "dep:axum", "dioxus/ssr"]);
+        assert!(check_features(&config, "fullstack.server_features", &defined).is_ok());
+    }
+
+    #[test]
+    fn missing_features_are_rejected() {
+        let config = fullstack_config();
+        let err = check_features(&config, "fullstack.client_features", &features(&["web"]))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("`web`"));
+        assert!(err.contains("fullstack.client_features"));
+
+        // only optional dependencies can be enabled as features
+        let serde = features(&["serde"]);
+        assert!(check_features(&config, "fullstack.server_features", &serde).is_err());
+    }
+}
@@ -92,3 +92,48 @@ impl Metadata {
         Err(Error::CargoError("InvalidOutput".to_string()))
     }
 }
+
+/// Returns the version of `package` recorded in the `Cargo.lock` under `workspace_dir`
+///
+/// If the lockfile is missing, can't be parsed, or resolves the package more than once, this returns `None`
+pub fn locked_package_version(workspace_dir: &Path, package: &str) -> Option<String> {
+    let lockfile = fs::read_to_string(workspace_dir.join("Cargo.lock")).ok()?;
+    let lockfile = lockfile.parse::<toml::Value>().ok()?;
+
+    let mut versions = lockfile
+        .get("package")?
+        .as_array()?
+        .iter()
+        .filter(|pkg| pkg.get("name").and_then(|v| v.as_str()) == Some(package))
+        .filter_map(|pkg| pkg.get("version").and_then(|v| v.as_str()));
+
+    let version = versions.next()?;
+    if versions.next().is_some() {
+        return None;
+    }
+    Some(version.to_string())
+}
+
+/// Returns the directories of the local crates `crate_dir` can be built from
+///
+/// These are the members of its workspace and its `path` dependencies, without `crate_dir` itself.
+pub fn local_crate_dirs(crate_dir: &Path) -> Result<Vec<PathBuf>> {
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .current_dir(crate_dir)
+        .exec()
+        .map_err(|e| Error::CargoError(e.to_string()))?;
+
+    let crate_dir = crate_dir.canonicalize()?;
+    let mut dirs: Vec<PathBuf> = metadata
+        .packages
+        .iter()
+        // registry and git dependencies have a source, local crates don't
+        .filter(|package| package.source.is_none())
+        .filter_map(|package| package.manifest_path.parent())
+        .map(|dir| dir.as_std_path().to_path_buf())
+        .filter(|dir| dir.canonicalize().ok().as_ref() != Some(&crate_dir))
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    Ok(dirs)
+}
@@ -38,21 +38,59 @@ impl Build {
                 .clone()
         });
 
-        let _ = PluginManager::on_build_start(&crate_config, &platform);
+        if !self.build.watch {
+            return Self::run(&crate_config, &platform, self.build.prerender);
+        }
+
+        // in watch mode a failing build shouldn't stop the watcher
+        if let Err(e) = Self::run(&crate_config, &platform, self.build.prerender) {
+            log::error!("{}", e);
+        }
+
+        let (shutdown_tx, shutdown_rx) = std::sync::mpsc::channel();
+        let _ = ctrlc::set_handler(move || {
+            let _ = shutdown_tx.send(());
+        });
+
+        let watcher_config = crate_config.clone();
+        let prerender = self.build.prerender;
+        let _watcher = server::setup_file_watcher(&crate_config, move |paths| {
+            log::info!("🪁 Rebuild project, {} files changed", paths.len());
+            Self::run(&watcher_config, &platform, prerender)
+        })?;
 
-        match platform.as_str() {
+        log::info!("👀 Watching for changes, press Ctrl-C to stop");
+        let _ = shutdown_rx.recv();
+
+        Ok(())
+    }
+
+    /// Run a single build, including the plugin hooks around it
+    fn run(crate_config: &CrateConfig, platform: &str, prerender: bool) -> Result<()> {
+        let build_start = std::time::SystemTime::now();
+        PluginManager::on_build_start(crate_config, platform)?;
+
+        match platform {
             "web" => {
-                crate::builder::build(&crate_config, false)?;
+                crate::builder::build(crate_config, false)?;
             }
             "desktop" => {
-                crate::builder::build_desktop(&crate_config, false)?;
+                crate::builder::build_desktop(crate_config, false)?;
+            }
+            "fullstack" => {
+                let (_, server) = crate::builder::build_fullstack(crate_config, false)?;
+                std::fs::copy(
+                    &server,
+                    crate_config.out_dir.join(server.file_name().unwrap()),
+                )?;
             }
             _ => {
                 return custom_error!("Unsupported platform target.");
             }
         }
 
-        let temp = gen_page(&crate_config.dioxus_config, false);
+        let temp = gen_page(&crate_config.dioxus_config, None);
+        let temp = PluginManager::transform_html(crate_config, temp, false)?;
 
         let mut file = std::fs::File::create(
             crate_config
@@ -69,7 +107,14 @@ impl Build {
         )?;
         file.write_all(temp.as_bytes())?;
 
-        let _ = PluginManager::on_build_finish(&crate_config, &platform);
+        if platform == "web" || platform == "fullstack" {
+            if prerender {
+                crate::prerender::prerender(crate_config, &temp)?;
+            }
+            crate::pwa::generate(crate_config, build_start)?;
+        }
+
+        PluginManager::on_build_finish(crate_config, platform)?;
 
         Ok(())
     }
@@ -25,13 +25,23 @@ pub struct ConfigOptsBuild {
     #[clap(long)]
     pub profile: Option<String>,
 
-    /// Build platform: support Web & Desktop [default: "default_platform"]
+    /// Build platform: support Web, Desktop & Fullstack [default: "default_platform"]
     #[clap(long)]
     pub platform: Option<String>,
 
     /// Space separated list of features to activate
     #[clap(long)]
     pub features: Option<Vec<String>>,
+
+    /// Render the `[web.prerender]` routes to static HTML [default: false]
+    #[clap(long)]
+    #[serde(default)]
+    pub prerender: bool,
+
+    /// Rebuild whenever a file in `web.watcher.watch_path` changes [default: false]
+    #[clap(long)]
+    #[serde(default)]
+    pub watch: bool,
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Parser)]
@@ -68,7 +78,7 @@ pub struct ConfigOptsServe {
     #[clap(long)]
     pub profile: Option<String>,
 
-    /// Build platform: support Web & Desktop [default: "default_platform"]
+    /// Build platform: support Web, Desktop & Fullstack [default: "default_platform"]
     #[clap(long)]
     pub platform: Option<String>,
 
@@ -77,6 +87,20 @@ pub struct ConfigOptsServe {
     #[serde(default)]
     pub hot_reload: bool,
 
+    /// The network interface name or address to show the network url and QR code for
+    #[clap(long)]
+    pub network: Option<String>,
+
+    /// Don't print a QR code of the network url [default: false]
+    #[clap(long)]
+    #[serde(default)]
+    pub no_qr: bool,
+
+    /// Show an interactive terminal UI with build status, logs and key bindings [default: false]
+    #[clap(short, long)]
+    #[serde(default)]
+    pub interactive: bool,
+
     /// Set cross-origin-policy to same-origin [default: false]
     #[clap(name = "cross-origin-policy")]
     #[clap(long)]
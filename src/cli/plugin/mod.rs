@@ -1,4 +1,5 @@
 use super::*;
+use crate::plugin::registry::{self, InstalledPlugin, PluginIndex};
 
 /// Build the Rust WASM app and all of its assets.
 #[derive(Clone, Debug, Deserialize, Subcommand)]
@@ -8,8 +9,45 @@ pub enum Plugin {
     List {},
     /// Get default app install path.
     AppPath {},
-    /// Install a new tool.
-    Add { name: String },
+    /// Install a plugin from the index, a git repository or a local directory.
+    Add {
+        /// `<name|git-url|path>[@version]`, local paths start with `./` or `../` or are absolute.
+        name: String,
+        /// Plugin index to use, a url or a file path.
+        #[clap(long)]
+        index: Option<String>,
+    },
+    /// Uninstall a plugin.
+    Remove { name: String },
+    /// Update one or all installed plugins.
+    Update {
+        name: Option<String>,
+        /// Plugin index to use, a url or a file path.
+        #[clap(long)]
+        index: Option<String>,
+    },
+    /// Show the details of a plugin.
+    Info {
+        name: String,
+        /// Plugin index to use, a url or a file path.
+        #[clap(long)]
+        index: Option<String>,
+    },
+    /// Search the plugin index.
+    Search {
+        query: Option<String>,
+        /// Plugin index to use, a url or a file path.
+        #[clap(long)]
+        index: Option<String>,
+    },
+    /// Run the hooks and `tests/*.lua` of a plugin against a fixture project.
+    Test {
+        /// The plugin directory.
+        #[clap(default_value = ".")]
+        dir: PathBuf,
+        /// Only run the tests whose name contains this.
+        filter: Option<String>,
+    },
 }
 
 impl Plugin {
@@ -21,15 +59,77 @@ impl Plugin {
                 }
             }
             Plugin::AppPath {} => {
-                let plugin_dir = crate::plugin::PluginManager::init_plugin_dir();
+                let plugin_dir = registry::plugins_dir();
                 if let Some(v) = plugin_dir.to_str() {
                     println!("{}", v);
                 } else {
                     log::error!("Plugin path get failed.");
                 }
             }
-            Plugin::Add { name: _ } => {
-                log::info!("You can use `dioxus plugin app-path` to get Installation position");
+            Plugin::Add { name, index } => {
+                let installed = registry::add(&name, index.as_deref()).await?;
+                log::info!("✅ Installed plugin `{}`", installed.name);
+            }
+            Plugin::Remove { name } => {
+                registry::remove(&name)?;
+                log::info!("🗑️  Removed plugin `{name}`");
+            }
+            Plugin::Update { name, index } => {
+                registry::update(name.as_deref(), index.as_deref()).await?;
+            }
+            Plugin::Info { name, index } => {
+                let installed = InstalledPlugin::load(&registry::plugin_path(&name)?);
+                let index = PluginIndex::load(index.as_deref()).await;
+                let entry = index.as_ref().ok().and_then(|index| index.find(&name));
+
+                if installed.is_none() && entry.is_none() {
+                    if let Err(err) = index {
+                        log::warn!("{err}");
+                    }
+                    return custom_error!("Plugin `{name}` not found.");
+                }
+
+                println!("{name}");
+                if let Some(entry) = entry {
+                    if let Some(description) = &entry.description {
+                        println!("  {description}");
+                    }
+                    println!("  repository: {}", entry.repository);
+                    let versions: Vec<&str> =
+                        entry.version.iter().map(|v| v.version.as_str()).collect();
+                    println!("  versions: {}", versions.join(", "));
+                }
+                if let Some(installed) = installed {
+                    let version = installed.version.or(installed.rev).unwrap_or_default();
+                    println!("  installed: {} {version}", installed.source);
+                    println!("  checksum: {}", installed.checksum);
+                }
+            }
+            Plugin::Search { query, index } => {
+                let index = PluginIndex::load(index.as_deref()).await?;
+                let installed = registry::installed_plugins();
+                for entry in index.search(query.as_deref().unwrap_or_default()) {
+                    let latest = entry
+                        .latest()
+                        .map(|v| format!(" v{}", v.version))
+                        .unwrap_or_default();
+                    let mark = if installed.iter().any(|p| p.name == entry.name) {
+                        " [installed]"
+                    } else {
+                        ""
+                    };
+                    println!(
+                        "- {}{latest}{mark}: {}",
+                        entry.name,
+                        entry.description.as_deref().unwrap_or_default()
+                    );
+                }
+            }
+            Plugin::Test { dir, filter } => {
+                let failed = crate::plugin::testing::run(&dir, filter.as_deref())?;
+                if failed > 0 {
+                    return custom_error!("{failed} plugin tests failed.");
+                }
             }
         }
         Ok(())
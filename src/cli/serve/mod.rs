@@ -1,9 +1,11 @@
 use super::*;
+use crate::plugin::PluginManager;
+use crate::tui::{self, TuiAction, TuiEvent};
 use std::{
     fs::create_dir_all,
-    io::Write,
+    io::{BufRead, BufReader, Read, Write},
     path::PathBuf,
-    process::{Command, Stdio},
+    process::{Child, Command, Stdio},
 };
 
 /// Run the WASM project on dev-server
@@ -20,6 +22,9 @@ impl Serve {
 
         // change the relase state.
         crate_config.with_hot_reload(self.serve.hot_reload);
+        crate_config.with_interactive(self.serve.interactive);
+        crate_config.with_network(self.serve.network.clone());
+        crate_config.with_qr_code(!self.serve.no_qr);
         crate_config.with_cross_origin_policy(self.serve.cross_origin_policy);
         crate_config.with_release(self.serve.release);
         crate_config.with_verbose(self.serve.verbose);
@@ -50,51 +55,180 @@ impl Serve {
         if platform.as_str() == "desktop" {
             crate::builder::build_desktop(&crate_config, true)?;
 
-            match &crate_config.executable {
-                crate::ExecutableType::Binary(name)
-                | crate::ExecutableType::Lib(name)
-                | crate::ExecutableType::Example(name) => {
-                    let mut file = crate_config.out_dir.join(name);
-                    if cfg!(windows) {
-                        file.set_extension("exe");
-                    }
-                    Command::new(file.to_str().unwrap())
-                        .stdout(Stdio::inherit())
-                        .output()?;
-                }
+            if crate_config.interactive {
+                return Serve::serve_desktop_interactive(&crate_config);
+            }
+            // ctrl-c reaches the app as well, wait for it to exit and shut down after it
+            let _ = ctrlc::set_handler(|| {});
+            let result = Command::new(desktop_binary(&crate_config))
+                .stdout(Stdio::inherit())
+                .output();
+            let _ = PluginManager::on_serve_shutdown(&crate_config);
+            result?;
+            return Ok(());
+        } else if platform == "fullstack" {
+            if crate_config.interactive {
+                log::warn!("The interactive UI isn't supported for fullstack apps yet.");
+                crate_config.with_interactive(false);
             }
+            server::fullstack::startup(self.serve.port, crate_config.clone(), self.serve.open)
+                .await?;
             return Ok(());
         } else if platform != "web" {
             return custom_error!("Unsupported platform target.");
         }
 
-        // generate dev-index page
-        Serve::regen_dev_page(&crate_config)?;
-
         // start the develop server
         server::startup(self.serve.port, crate_config.clone(), self.serve.open).await?;
 
         Ok(())
     }
 
-    pub fn regen_dev_page(crate_config: &CrateConfig) -> Result<()> {
-        let serve_html = gen_page(&crate_config.dioxus_config, true);
+    /// Run the desktop app under the interactive UI, rebuilding and restarting it on demand
+    fn serve_desktop_interactive(crate_config: &CrateConfig) -> Result<()> {
+        let info = vec![("Platform".to_string(), "Desktop".to_string())];
+        let actions = tui::start(info, None)?;
+        let mut app = None;
+        let result = Serve::run_desktop_actions(crate_config, actions, &mut app);
+
+        if let Some(mut app) = app {
+            let _ = app.kill();
+        }
+        tui::stop();
+        let _ = PluginManager::on_serve_shutdown(crate_config);
+        result
+    }
+
+    /// Handle the key presses of the interactive UI until the user quits
+    fn run_desktop_actions(
+        crate_config: &CrateConfig,
+        actions: std::sync::mpsc::Receiver<TuiAction>,
+        app: &mut Option<Child>,
+    ) -> Result<()> {
+        *app = Some(spawn_desktop_app(crate_config)?);
+
+        for action in actions {
+            match action {
+                TuiAction::Rebuild => {
+                    if let Some(mut app) = app.take() {
+                        let _ = app.kill();
+                        let _ = app.wait();
+                    }
+                    tui::send(TuiEvent::BuildStarted);
+                    let t_start = std::time::Instant::now();
+                    match crate::builder::build_desktop(crate_config, true) {
+                        Ok(()) => {
+                            tui::send(TuiEvent::BuildFinished {
+                                changed: vec![],
+                                warnings: vec![],
+                                elapsed_time: t_start.elapsed().as_millis(),
+                            });
+                            *app = Some(spawn_desktop_app(crate_config)?);
+                        }
+                        Err(err) => {
+                            tui::send(TuiEvent::BuildFailed);
+                            log::error!("{}", err);
+                        }
+                    }
+                }
+                TuiAction::OpenBrowser => log::warn!("Desktop apps don't run in a browser."),
+                TuiAction::ToggleHotReload => {}
+                TuiAction::Quit => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the dev page of the build `build_id` to the output dir
+    pub fn regen_dev_page(crate_config: &CrateConfig, build_id: u64) -> Result<()> {
+        let serve_html = gen_page(&crate_config.dioxus_config, Some(build_id));
+        let serve_html = PluginManager::transform_html(crate_config, serve_html, true)?;
+
+        let index_path = dev_page_path(crate_config);
+        if let Some(dist_path) = index_path.parent() {
+            create_dir_all(dist_path)?;
+        }
+        let mut file = std::fs::File::create(index_path)?;
+        file.write_all(serve_html.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Point the dev page at the new build `build_id`
+    ///
+    /// The page is only generated again if `reload_html` is set, otherwise just its build id
+    /// changes.
+    pub fn update_dev_page(crate_config: &CrateConfig, build_id: u64) -> Result<()> {
+        if crate_config
+            .dioxus_config
+            .web
+            .watcher
+            .reload_html
+            .unwrap_or(false)
+        {
+            return Serve::regen_dev_page(crate_config, build_id);
+        }
+
+        let index_path = dev_page_path(crate_config);
+        let html = std::fs::read_to_string(&index_path)?;
+        let build_id_attr = regex::Regex::new(r#"data-build-id="\d*""#).unwrap();
+        let html = build_id_attr.replace(&html, format!("data-build-id=\"{build_id}\""));
+        std::fs::write(index_path, html.as_bytes())?;
 
-        let dist_path = crate_config.crate_dir.join(
+        Ok(())
+    }
+}
+
+/// The `index.html` served by the dev server
+fn dev_page_path(crate_config: &CrateConfig) -> PathBuf {
+    crate_config
+        .crate_dir
+        .join(
             crate_config
                 .dioxus_config
                 .application
                 .out_dir
                 .clone()
                 .unwrap_or_else(|| PathBuf::from("dist")),
-        );
-        if !dist_path.is_dir() {
-            create_dir_all(&dist_path)?;
-        }
-        let index_path = dist_path.join("index.html");
-        let mut file = std::fs::File::create(index_path)?;
-        file.write_all(serve_html.as_bytes())?;
+        )
+        .join("index.html")
+}
 
-        Ok(())
+/// The executable `build_desktop` copies into the output dir
+fn desktop_binary(crate_config: &CrateConfig) -> PathBuf {
+    let name = match &crate_config.executable {
+        crate::ExecutableType::Binary(name)
+        | crate::ExecutableType::Lib(name)
+        | crate::ExecutableType::Example(name) => name,
+    };
+    let mut file = crate_config.out_dir.join(name);
+    if cfg!(windows) {
+        file.set_extension("exe");
     }
+    file
+}
+
+/// Start the desktop app, forwarding its output to the interactive UI
+fn spawn_desktop_app(crate_config: &CrateConfig) -> Result<Child> {
+    let mut app = Command::new(desktop_binary(crate_config))
+        .current_dir(&crate_config.crate_dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    fn forward(output: impl Read + Send + 'static) {
+        std::thread::spawn(move || {
+            for line in BufReader::new(output).lines().map_while(|line| line.ok()) {
+                tui::send(TuiEvent::AppLog(line));
+            }
+        });
+    }
+    if let Some(stdout) = app.stdout.take() {
+        forward(stdout);
+    }
+    if let Some(stderr) = app.stderr.take() {
+        forward(stderr);
+    }
+
+    Ok(app)
 }
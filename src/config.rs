@@ -10,6 +10,8 @@ pub struct DioxusConfig {
 
     #[serde(default = "default_plugin")]
     pub plugin: toml::Value,
+
+    pub fullstack: Option<FullstackConfig>,
 }
 
 fn default_plugin() -> toml::Value {
@@ -68,6 +70,9 @@ impl Default for DioxusConfig {
                     watch_path: Some(vec![PathBuf::from("src")]),
                     reload_html: Some(false),
                     index_on_404: Some(true),
+                    ignore: None,
+                    include: None,
+                    gitignore: None,
                 },
                 resource: WebResourceConfig {
                     dev: WebDevResourceConfig {
@@ -77,8 +82,13 @@ impl Default for DioxusConfig {
                     style: Some(vec![]),
                     script: Some(vec![]),
                 },
+                bindgen: WebBindgenConfig::default(),
+                entry: vec![],
+                pwa: None,
+                prerender: None,
             },
             plugin: toml::Value::Table(toml::map::Map::new()),
+            fullstack: None,
         }
     }
 }
@@ -101,6 +111,25 @@ pub struct WebConfig {
     pub proxy: Option<Vec<WebProxyConfig>>,
     pub watcher: WebWatcherConfig,
     pub resource: WebResourceConfig,
+    #[serde(default)]
+    pub bindgen: WebBindgenConfig,
+    #[serde(default)]
+    pub entry: Vec<WebEntryConfig>,
+    pub pwa: Option<WebPwaConfig>,
+    pub prerender: Option<WebPrerenderConfig>,
+}
+
+impl WebConfig {
+    /// The wasm entries to build for the web platform.
+    ///
+    /// Without any `[[web.entry]]` table this is a single main entry for the crate executable.
+    pub fn entries(&self) -> Vec<WebEntryConfig> {
+        if self.entry.is_empty() {
+            vec![WebEntryConfig::default()]
+        } else {
+            self.entry.clone()
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -119,6 +148,12 @@ pub struct WebWatcherConfig {
     pub watch_path: Option<Vec<PathBuf>>,
     pub reload_html: Option<bool>,
     pub index_on_404: Option<bool>,
+    /// Glob patterns, relative to the crate dir, of files that never trigger a rebuild
+    pub ignore: Option<Vec<String>>,
+    /// If set, only files matching one of these glob patterns trigger a rebuild
+    pub include: Option<Vec<String>>,
+    /// Skip the files ignored by the crate's `.gitignore` [default: true]
+    pub gitignore: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -134,6 +169,180 @@ pub struct WebDevResourceConfig {
     pub script: Option<Vec<PathBuf>>,
 }
 
+/// Options passed to `wasm-bindgen` when generating the JS glue for the wasm module.
+///
+/// Every field is optional: unset debug related options fall back to `true` in
+/// debug builds and to the size-optimized `false` in release builds.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebBindgenConfig {
+    pub debug: Option<bool>,
+    pub demangle: Option<bool>,
+    pub keep_debug: Option<bool>,
+    pub reference_types: Option<bool>,
+    pub weak_refs: Option<bool>,
+    pub typescript: Option<bool>,
+    pub split_linked_modules: Option<bool>,
+}
+
+impl WebBindgenConfig {
+    pub fn debug(&self, release: bool) -> bool {
+        self.debug.unwrap_or(!release)
+    }
+
+    pub fn demangle(&self) -> bool {
+        self.demangle.unwrap_or(true)
+    }
+
+    pub fn keep_debug(&self, release: bool) -> bool {
+        self.keep_debug.unwrap_or(!release)
+    }
+
+    pub fn reference_types(&self) -> bool {
+        self.reference_types.unwrap_or(false)
+    }
+
+    pub fn weak_refs(&self) -> bool {
+        self.weak_refs.unwrap_or(false)
+    }
+
+    pub fn typescript(&self) -> bool {
+        self.typescript.unwrap_or(false)
+    }
+
+    pub fn split_linked_modules(&self) -> bool {
+        self.split_linked_modules.unwrap_or(false)
+    }
+}
+
+/// A wasm module built for the web platform, declared with `[[web.entry]]`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebEntryConfig {
+    /// The bin target to build, defaults to the crate executable
+    pub bin: Option<String>,
+    /// Build an example target instead of a bin
+    pub example: Option<String>,
+    /// The name of the generated `{out_name}.js` and `{out_name}_bg.wasm` files
+    pub out_name: Option<String>,
+    #[serde(default)]
+    pub role: WebEntryRole,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebEntryRole {
+    /// Loaded by `index.html` and mounts the app
+    #[default]
+    Main,
+    /// Started by the app with `new Worker(...)`
+    Worker,
+    /// Registered by `index.html` with `navigator.serviceWorker.register(...)`
+    ServiceWorker,
+}
+
+impl WebEntryConfig {
+    pub fn executable(&self, default: &ExecutableType) -> ExecutableType {
+        if let Some(example) = &self.example {
+            ExecutableType::Example(example.clone())
+        } else if let Some(bin) = &self.bin {
+            ExecutableType::Binary(bin.clone())
+        } else {
+            default.clone()
+        }
+    }
+
+    pub fn out_name(&self, app_name: &str) -> String {
+        if let Some(name) = &self.out_name {
+            return name.clone();
+        }
+        match self.role {
+            WebEntryRole::Main => app_name.to_string(),
+            _ => self
+                .bin
+                .clone()
+                .or_else(|| self.example.clone())
+                .unwrap_or_else(|| app_name.to_string()),
+        }
+    }
+
+    /// Path of the generated script that boots a worker entry, relative to `out_dir`.
+    ///
+    /// Service workers live at the root so their scope covers the whole app.
+    pub fn bootstrap_path(&self, app_name: &str) -> Option<PathBuf> {
+        let file_name = format!("{}_worker.js", self.out_name(app_name));
+        match self.role {
+            WebEntryRole::Main => None,
+            WebEntryRole::Worker => Some(PathBuf::from("assets").join("dioxus").join(file_name)),
+            WebEntryRole::ServiceWorker => Some(PathBuf::from(file_name)),
+        }
+    }
+}
+
+/// Progressive web app support, enabled by the presence of a `[web.pwa]` table.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebPwaConfig {
+    pub name: Option<String>,
+    pub short_name: Option<String>,
+    pub description: Option<String>,
+    /// A square source image in `asset_dir` that the app icons are resized from
+    pub icon: Option<PathBuf>,
+    pub icon_sizes: Option<Vec<u32>>,
+    pub theme_color: Option<String>,
+    pub background_color: Option<String>,
+    pub start_url: Option<String>,
+    pub display: Option<String>,
+    #[serde(default)]
+    pub offline_strategy: PwaOfflineStrategy,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PwaOfflineStrategy {
+    /// Serve from the precache and only hit the network for uncached requests
+    #[default]
+    CacheFirst,
+    /// Always try the network and fall back to the cache when offline
+    NetworkFirst,
+}
+
+/// Routes rendered to static HTML by `dioxus build --prerender`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebPrerenderConfig {
+    pub routes: Option<Vec<String>>,
+    /// Ask the app binary for the list of routes to render
+    pub discover: Option<bool>,
+    /// Extra features enabled for the host build, e.g. the one pulling in `dioxus-ssr`
+    pub features: Option<Vec<String>>,
+}
+
+/// The `fullstack` platform builds a server binary next to the wasm client.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FullstackConfig {
+    /// The bin target of the server, defaults to the crate executable
+    pub server_bin: Option<String>,
+    /// Extra features enabled for the server build, e.g. `["ssr"]`
+    pub server_features: Option<Vec<String>>,
+    /// Extra features enabled for the wasm client build, e.g. `["web"]`
+    pub client_features: Option<Vec<String>>,
+    /// The address the server listens on while serving [default: "127.0.0.1:8081"]
+    pub server_addr: Option<String>,
+}
+
+impl FullstackConfig {
+    pub fn server_features(&self) -> Vec<String> {
+        self.server_features.clone().unwrap_or_default()
+    }
+
+    pub fn client_features(&self) -> Vec<String> {
+        self.client_features.clone().unwrap_or_default()
+    }
+
+    pub fn server_addr(&self) -> String {
+        self.server_addr
+            .clone()
+            .unwrap_or_else(|| "127.0.0.1:8081".to_string())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CrateConfig {
     pub out_dir: PathBuf,
@@ -146,6 +355,9 @@ pub struct CrateConfig {
     pub dioxus_config: DioxusConfig,
     pub release: bool,
     pub hot_reload: bool,
+    pub interactive: bool,
+    pub network: Option<String>,
+    pub qr_code: bool,
     pub cross_origin_policy: bool,
     pub verbose: bool,
     pub custom_profile: Option<String>,
@@ -220,6 +432,9 @@ impl CrateConfig {
             release,
             dioxus_config,
             hot_reload,
+            interactive: false,
+            network: None,
+            qr_code: true,
             cross_origin_policy: false,
             custom_profile,
             features,
@@ -242,6 +457,21 @@ impl CrateConfig {
         self
     }
 
+    pub fn with_interactive(&mut self, interactive: bool) -> &mut Self {
+        self.interactive = interactive;
+        self
+    }
+
+    pub fn with_network(&mut self, network: Option<String>) -> &mut Self {
+        self.network = network;
+        self
+    }
+
+    pub fn with_qr_code(&mut self, qr_code: bool) -> &mut Self {
+        self.qr_code = qr_code;
+        self
+    }
+
     pub fn with_cross_origin_policy(&mut self, cross_origin_policy: bool) -> &mut Self {
         self.cross_origin_policy = cross_origin_policy;
         self
@@ -262,3 +492,34 @@ impl CrateConfig {
         self
     }
 }
+
+#[cfg(test)]
+impl CrateConfig {
+    /// The config of a binary crate named `app` in `crate_dir`, without reading anything from disk
+    pub fn for_test(crate_dir: &std::path::Path) -> Self {
+        let mut dioxus_config = DioxusConfig::default();
+        dioxus_config.application.name = "app".into();
+        let manifest =
+            cargo_toml::Manifest::from_str("[package]\nname = \"app\"\nversion = \"0.1.0\"\n")
+                .unwrap();
+        Self {
+            out_dir: crate_dir.join("dist"),
+            crate_dir: crate_dir.to_path_buf(),
+            workspace_dir: crate_dir.to_path_buf(),
+            target_dir: crate_dir.join("target"),
+            asset_dir: crate_dir.join("public"),
+            manifest,
+            executable: ExecutableType::Binary("app".into()),
+            dioxus_config,
+            release: false,
+            hot_reload: false,
+            interactive: false,
+            network: None,
+            qr_code: false,
+            cross_origin_policy: false,
+            verbose: false,
+            custom_profile: None,
+            features: None,
+        }
+    }
+}
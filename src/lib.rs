@@ -22,3 +22,9 @@ pub mod logging;
 pub use logging::*;
 
 pub mod plugin;
+
+pub mod prerender;
+
+pub mod pwa;
+
+pub mod tui;
@@ -1,4 +1,23 @@
 use fern::colors::{Color, ColoredLevelConfig};
+use std::sync::Mutex;
+
+/// Receives every log line instead of stdout while it is set
+pub type LogSink = Box<dyn Fn(log::Level, String) + Send>;
+
+lazy_static::lazy_static! {
+    static ref LOG_SINK: Mutex<Option<LogSink>> = Mutex::new(None);
+}
+
+/// Send log lines to `sink` instead of stdout, or back to stdout with `None`
+///
+/// Used while something else owns the terminal, like the interactive serve UI.
+pub fn redirect_logs(sink: Option<LogSink>) {
+    *LOG_SINK.lock().unwrap() = sink;
+}
+
+fn is_redirected() -> bool {
+    LOG_SINK.lock().unwrap().is_some()
+}
 
 pub fn set_up_logging() {
     // configure colors for the whole line
@@ -16,7 +35,8 @@ pub fn set_up_logging() {
     // just clone `colors_line` and overwrite our changes
     let colors_level = colors_line.info(Color::Green);
     // here we set up our fern Dispatch
-    fern::Dispatch::new()
+    let stdout = fern::Dispatch::new()
+        .filter(|_| !is_redirected())
         .format(move |out, message, record| {
             out.finish(format_args!(
                 "{color_line}[{level}{color_line}] {message}\x1B[0m",
@@ -28,8 +48,18 @@ pub fn set_up_logging() {
                 message = message,
             ));
         })
+        .chain(std::io::stdout());
+
+    let redirected = fern::Output::call(|record| {
+        if let Some(sink) = LOG_SINK.lock().unwrap().as_ref() {
+            sink(record.level(), record.args().to_string());
+        }
+    });
+
+    fern::Dispatch::new()
         .level(log::LevelFilter::Info)
-        .chain(std::io::stdout())
+        .chain(stdout)
+        .chain(redirected)
         .apply()
         .unwrap();
 }
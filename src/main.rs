@@ -1,12 +1,10 @@
 use anyhow::anyhow;
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Subcommand};
 use dioxus_cli::{plugin::PluginManager, *};
 use Commands::*;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let args = Cli::parse();
-
     set_up_logging();
 
     let dioxus_config = DioxusConfig::load()
@@ -16,8 +14,23 @@ async fn main() -> anyhow::Result<()> {
             DioxusConfig::default()
         });
 
-    PluginManager::init(dioxus_config.plugin)
-        .map_err(|e| anyhow!("🚫 Plugin system initialization failed: {e}"))?;
+    // a broken plugin shouldn't make the built-in commands unusable, `dioxus plugin` included
+    if let Err(e) = PluginManager::init(&dioxus_config).await {
+        log::warn!("🚫 Plugin system initialization failed, continuing without plugins: {e}");
+    }
+
+    // plugins can add their own subcommands, so they are loaded before parsing the arguments
+    let matches = PluginManager::augment_cli(Cli::command()).get_matches();
+    // built-in commands take precedence over the ones of plugins
+    let plugin_command = matches
+        .subcommand()
+        .filter(|(name, _)| !Commands::has_subcommand(name));
+    if let Some((name, plugin_matches)) = plugin_command {
+        if let Some(result) = PluginManager::run_command(name, plugin_matches) {
+            return result.map_err(|e| anyhow!("🚫 Plugin command failed: {e}"));
+        }
+    }
+    let args = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
 
     match args.action {
         Translate(opts) => opts
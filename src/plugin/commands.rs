@@ -0,0 +1,299 @@
+//! CLI subcommands registered by plugins
+//!
+//! A plugin lists its commands in `manager.commands`, they are surfaced as
+//! `dioxus <plugin> <command> [args]`:
+//!
+//! ```lua
+//! manager.commands = {
+//!     icons = {
+//!         about = "Generate the app icons",
+//!         args = {
+//!             { name = "input", help = "The source image", required = true },
+//!             { name = "size", short = "s", long = true, default = "64" },
+//!             { name = "force", long = true, flag = true },
+//!         },
+//!         run = function (ctx, args)
+//!             log.info("Generating icons from " .. args.input .. " into " .. ctx.out_dir)
+//!         end,
+//!     },
+//! }
+//! ```
+use clap::{Arg, ArgAction, ArgMatches};
+use convert_case::{Case, Casing};
+use mlua::{FromLua, Lua, Table};
+
+/// Argument names clap uses itself
+const RESERVED_NAMES: &[&str] = &["help", "h", "version"];
+/// Short flags clap uses itself
+const RESERVED_SHORTS: &[char] = &['h', 'V'];
+
+#[derive(Debug, Clone)]
+pub struct PluginCommand {
+    pub name: String,
+    pub about: Option<String>,
+    pub args: Vec<PluginCommandArg>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PluginCommandArg {
+    pub name: String,
+    pub help: Option<String>,
+    pub short: Option<char>,
+    /// An option like `--size <size>` instead of a positional argument
+    pub long: bool,
+    pub required: bool,
+    /// A boolean switch like `--force`
+    pub flag: bool,
+    /// Can be given more than once, the plugin gets a list
+    pub multiple: bool,
+    pub default: Option<String>,
+}
+
+impl<'lua> FromLua<'lua> for PluginCommandArg {
+    fn from_lua(lua_value: mlua::Value<'lua>, _lua: &'lua Lua) -> mlua::Result<Self> {
+        let mut res = Self::default();
+        if let mlua::Value::Table(tab) = lua_value {
+            if let Ok(v) = tab.get::<_, String>("name") {
+                res.name = v;
+            }
+            if let Ok(v) = tab.get::<_, String>("help") {
+                res.help = Some(v);
+            }
+            if let Ok(v) = tab.get::<_, String>("short") {
+                res.short = v.chars().next();
+            }
+            if let Ok(v) = tab.get::<_, bool>("long") {
+                res.long = v;
+            }
+            if let Ok(v) = tab.get::<_, bool>("required") {
+                res.required = v;
+            }
+            if let Ok(v) = tab.get::<_, bool>("flag") {
+                res.flag = v;
+            }
+            if let Ok(v) = tab.get::<_, bool>("multiple") {
+                res.multiple = v;
+            }
+            if let Ok(v) = tab.get::<_, String>("default") {
+                res.default = Some(v);
+            }
+        }
+        Ok(res)
+    }
+}
+
+impl PluginCommand {
+    /// Read the commands of the `manager` table of a plugin
+    ///
+    /// Commands and arguments clap would reject are skipped with a warning.
+    pub fn from_manager(manager: &Table) -> Vec<Self> {
+        let Ok(commands) = manager.get::<_, Table>("commands") else {
+            return vec![];
+        };
+        let mut res: Vec<Self> = commands
+            .pairs::<String, Table>()
+            .filter_map(|pair| pair.ok())
+            .filter(|(name, _)| {
+                let valid = !name.is_empty() && name != "help";
+                if !valid {
+                    log::warn!("Skipping the plugin command `{name}`, the name isn't allowed.");
+                }
+                valid
+            })
+            .map(|(name, spec)| {
+                let args = spec
+                    .get::<_, Vec<PluginCommandArg>>("args")
+                    .unwrap_or_default();
+                Self {
+                    args: valid_args(&name, args),
+                    about: spec.get("about").ok(),
+                    name,
+                }
+            })
+            .collect();
+        res.sort_by(|a, b| a.name.cmp(&b.name));
+        res
+    }
+
+    pub fn to_clap(&self) -> clap::Command {
+        let mut command = clap::Command::new(self.name.clone());
+        if let Some(about) = &self.about {
+            command = command.about(about.clone());
+        }
+        for spec in &self.args {
+            let mut arg = Arg::new(spec.name.clone()).required(spec.required);
+            if let Some(help) = &spec.help {
+                arg = arg.help(help.clone());
+            }
+            if spec.long || spec.flag {
+                arg = arg.long(spec.name.clone());
+            }
+            if let Some(short) = spec.short {
+                arg = arg.short(short);
+            }
+            arg = if spec.flag {
+                arg.action(ArgAction::SetTrue)
+            } else if spec.multiple {
+                arg.action(ArgAction::Append)
+            } else {
+                arg.action(ArgAction::Set)
+            };
+            if let Some(default) = &spec.default {
+                arg = arg.default_value(default.clone());
+            }
+            command = command.arg(arg);
+        }
+        command
+    }
+
+    /// The arguments given on the command line, as the table the `run` function gets
+    pub fn args_table<'lua>(
+        &self,
+        lua: &'lua Lua,
+        matches: &ArgMatches,
+    ) -> mlua::Result<Table<'lua>> {
+        let args = lua.create_table()?;
+        for spec in &self.args {
+            let name = spec.name.as_str();
+            if spec.flag {
+                args.set(name, matches.get_flag(name))?;
+            } else if spec.multiple {
+                let values: Vec<String> = matches
+                    .get_many::<String>(name)
+                    .map(|values| values.cloned().collect())
+                    .unwrap_or_default();
+                args.set(name, values)?;
+            } else if let Some(value) = matches.get_one::<String>(name) {
+                args.set(name, value.clone())?;
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// The `args` of the plugin command `command` without the ones that would make clap panic
+fn valid_args(command: &str, args: Vec<PluginCommandArg>) -> Vec<PluginCommandArg> {
+    let mut res: Vec<PluginCommandArg> = vec![];
+    for arg in args {
+        let problem = if arg.name.is_empty() {
+            Some("it has no name")
+        } else if RESERVED_NAMES.contains(&arg.name.as_str()) {
+            Some("the name is reserved")
+        } else if res.iter().any(|other| other.name == arg.name) {
+            Some("the name is used twice")
+        } else if arg
+            .short
+            .map_or(false, |short| RESERVED_SHORTS.contains(&short))
+        {
+            Some("the short flag is reserved")
+        } else if arg.short.is_some() && res.iter().any(|other| other.short == arg.short) {
+            Some("the short flag is used twice")
+        } else {
+            None
+        };
+        match problem {
+            Some(problem) => log::warn!(
+                "Skipping the argument `{}` of the plugin command `{command}`, {problem}.",
+                arg.name
+            ),
+            None => res.push(arg),
+        }
+    }
+    res
+}
+
+/// The subcommand a plugin named `name` is available under, like `my-plugin`
+pub fn cli_name(name: &str) -> String {
+    name.to_case(Case::Kebab)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn load_commands(lua: &Lua, commands: &str) -> Vec<PluginCommand> {
+        let manager: Table = lua
+            .load(&format!("return {{ commands = {commands} }}"))
+            .eval()
+            .unwrap();
+        PluginCommand::from_manager(&manager)
+    }
+
+    #[test]
+    fn args_round_trip() {
+        let lua = Lua::new();
+        let commands = load_commands(
+            &lua,
+            r#"{
+                icons = {
+                    args = {
+                        { name = "input", required = true },
+                        { name = "size", short = "s", long = true, default = "64" },
+                        { name = "force", long = true, flag = true },
+                        { name = "exclude", long = true, multiple = true },
+                    },
+                },
+            }"#,
+        );
+        let icons = &commands[0];
+
+        let matches = icons
+            .to_clap()
+            .try_get_matches_from([
+                "icons",
+                "logo.png",
+                "--force",
+                "--exclude",
+                "a",
+                "--exclude",
+                "b",
+            ])
+            .unwrap();
+        let args = icons.args_table(&lua, &matches).unwrap();
+        assert_eq!(args.get::<_, String>("input").unwrap(), "logo.png");
+        assert_eq!(args.get::<_, String>("size").unwrap(), "64");
+        assert!(args.get::<_, bool>("force").unwrap());
+        assert_eq!(args.get::<_, Vec<String>>("exclude").unwrap(), ["a", "b"]);
+
+        let matches = icons
+            .to_clap()
+            .try_get_matches_from(["icons", "logo.png", "-s", "128"])
+            .unwrap();
+        let args = icons.args_table(&lua, &matches).unwrap();
+        assert_eq!(args.get::<_, String>("size").unwrap(), "128");
+        assert!(!args.get::<_, bool>("force").unwrap());
+        assert!(args.get::<_, Vec<String>>("exclude").unwrap().is_empty());
+
+        assert!(icons.to_clap().try_get_matches_from(["icons"]).is_err());
+    }
+
+    #[test]
+    fn invalid_specs_are_skipped() {
+        let lua = Lua::new();
+        let commands = load_commands(
+            &lua,
+            r#"{
+                help = {},
+                icons = {
+                    args = {
+                        { help = "no name" },
+                        { name = "help", long = true, flag = true },
+                        { name = "verbose", short = "V", flag = true },
+                        { name = "size", short = "s", long = true },
+                        { name = "size", long = true },
+                        { name = "scale", short = "s", long = true },
+                    },
+                },
+            }"#,
+        );
+        assert_eq!(commands.len(), 1);
+        let names: Vec<&str> = commands[0]
+            .args
+            .iter()
+            .map(|arg| arg.name.as_str())
+            .collect();
+        assert_eq!(names, ["size"]);
+        // clap asserts the command is valid when it's built
+        commands[0].to_clap().debug_assert();
+    }
+}
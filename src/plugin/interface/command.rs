@@ -1,24 +1,33 @@
-use std::process::{Command, Stdio};
+use std::process::{Output, Stdio};
 
-use mlua::{FromLua, UserData};
+use mlua::{FromLua, ToLua, UserData};
+use tokio::process::Command;
+
+use crate::plugin::permissions;
 
 enum StdioFromString {
     Inherit,
     Piped,
     Null,
 }
+/// Streams are piped unless the plugin asks for something else, so their output is captured
 impl<'lua> FromLua<'lua> for StdioFromString {
     fn from_lua(lua_value: mlua::Value<'lua>, _lua: &'lua mlua::Lua) -> mlua::Result<Self> {
-        if let mlua::Value::String(v) = lua_value {
-            let v = v.to_str().unwrap();
-            return Ok(match v.to_lowercase().as_str() {
-                "inherit" => Self::Inherit,
-                "piped" => Self::Piped,
-                "null" => Self::Null,
-                _ => Self::Inherit,
-            });
+        let mlua::Value::String(v) = lua_value else {
+            return Ok(Self::Piped);
+        };
+        match v.to_string_lossy().to_lowercase().as_str() {
+            "inherit" => Ok(Self::Inherit),
+            "piped" => Ok(Self::Piped),
+            "null" => Ok(Self::Null),
+            other => Err(mlua::Error::FromLuaConversionError {
+                from: "string",
+                to: "Stdio",
+                message: Some(format!(
+                    "expected \"inherit\", \"piped\" or \"null\", got \"{other}\""
+                )),
+            }),
         }
-        Ok(Self::Inherit)
     }
 }
 impl StdioFromString {
@@ -31,31 +40,45 @@ impl StdioFromString {
     }
 }
 
+/// The result of `exec`, streams that weren't piped are empty
+struct CommandOutput(Output);
+
+impl<'lua> ToLua<'lua> for CommandOutput {
+    fn to_lua(self, lua: &'lua mlua::Lua) -> mlua::Result<mlua::Value<'lua>> {
+        let res = lua.create_table()?;
+        // `nil` if the program was killed by a signal
+        res.set("code", self.0.status.code())?;
+        res.set("success", self.0.status.success())?;
+        res.set("stdout", lua.create_string(&self.0.stdout)?)?;
+        res.set("stderr", lua.create_string(&self.0.stderr)?)?;
+        Ok(mlua::Value::Table(res))
+    }
+}
+
 pub struct PluginCommander;
 impl UserData for PluginCommander {
     fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
-        methods.add_function(
+        methods.add_async_function(
             "exec",
-            |_, args: (Vec<String>, StdioFromString, StdioFromString)| {
+            |lua, args: (Vec<String>, StdioFromString, StdioFromString)| {
                 let cmd = args.0;
                 let stdout = args.1;
                 let stderr = args.2;
+                let allowed = match cmd.first() {
+                    Some(cmd_name) => permissions::current(lua).check_command(cmd_name),
+                    None => Ok(()),
+                };
 
-                if cmd.len() == 0 {
-                    return Ok(());
+                async move {
+                    allowed?;
+                    let Some(cmd_name) = cmd.first() else {
+                        return Ok(None);
+                    };
+                    let mut command = Command::new(cmd_name);
+                    command.args(&cmd[1..]);
+                    command.stdout(stdout.to_stdio()).stderr(stderr.to_stdio());
+                    Ok::<_, mlua::Error>(Some(CommandOutput(command.output().await?)))
                 }
-                let cmd_name = cmd.get(0).unwrap();
-                let mut command = Command::new(cmd_name);
-                let t = cmd
-                    .iter()
-                    .enumerate()
-                    .filter(|(i, _)| *i > 0)
-                    .map(|v| v.1.clone())
-                    .collect::<Vec<String>>();
-                command.args(t);
-                command.stdout(stdout.to_stdio()).stderr(stderr.to_stdio());
-                command.output()?;
-                Ok(())
             },
         );
     }
@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+
+use mlua::UserData;
+
+use crate::plugin::permissions;
+
+pub struct PluginEnv;
+impl UserData for PluginEnv {
+    fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_function("get", |lua, name: String| {
+            permissions::current(lua).check_env(&name)?;
+            Ok(std::env::var(name).ok())
+        });
+        // only the variables the plugin may read
+        methods.add_function("vars", |lua, ()| {
+            let sandbox = permissions::current(lua);
+            Ok(std::env::vars()
+                .filter(|(name, _)| sandbox.allows_env(name))
+                .collect::<BTreeMap<String, String>>())
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::plugin::permissions::{PluginPermissions, Sandbox};
+    use std::path::Path;
+
+    #[test]
+    fn only_allowed_vars() {
+        std::env::set_var("DIOXUS_ENV_TEST_ALLOWED", "yes");
+        std::env::set_var("DIOXUS_ENV_TEST_DENIED", "no");
+        let permissions = PluginPermissions {
+            env: vec!["DIOXUS_ENV_TEST_ALLOWED".into()],
+            ..Default::default()
+        };
+        let lua = mlua::Lua::new();
+        permissions::apply(
+            &lua,
+            Sandbox::new(&permissions, Path::new("/plugins/demo"), None, None),
+        );
+        lua.globals().set("env", PluginEnv).unwrap();
+
+        let vars: BTreeMap<String, String> = lua.load("return env.vars()").eval().unwrap();
+        assert_eq!(
+            vars.get("DIOXUS_ENV_TEST_ALLOWED").map(String::as_str),
+            Some("yes")
+        );
+        assert!(!vars.contains_key("DIOXUS_ENV_TEST_DENIED"));
+
+        let allowed: Option<String> = lua
+            .load(r#"return env.get("DIOXUS_ENV_TEST_ALLOWED")"#)
+            .eval()
+            .unwrap();
+        assert_eq!(allowed.as_deref(), Some("yes"));
+        assert!(lua
+            .load(r#"return env.get("DIOXUS_ENV_TEST_DENIED")"#)
+            .exec()
+            .is_err());
+    }
+}
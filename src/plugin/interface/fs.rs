@@ -1,21 +1,23 @@
 use std::{
     fs::{create_dir, create_dir_all, remove_dir_all, File},
-    io::{Read, Write},
-    path::PathBuf,
+    path::Path,
 };
 
-use crate::tools::extract_zip;
+use crate::{plugin::permissions, tools::extract_zip};
 use flate2::read::GzDecoder;
+use globset::Glob;
 use mlua::UserData;
 use tar::Archive;
+use walkdir::WalkDir;
+use zip::ZipArchive;
 
 pub struct PluginFileSystem;
 impl UserData for PluginFileSystem {
     fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
-        methods.add_function("create_dir", |_, args: (String, bool)| {
+        methods.add_function("create_dir", |lua, args: (String, bool)| {
             let path = args.0;
             let recursive = args.1;
-            let path = PathBuf::from(path);
+            let path = permissions::current(lua).check_path(&path)?;
             if !path.exists() {
                 let v = if recursive {
                     create_dir_all(path)
@@ -26,46 +28,66 @@ impl UserData for PluginFileSystem {
             }
             Ok(true)
         });
-        methods.add_function("remove_dir", |_, path: String| {
-            let path = PathBuf::from(path);
+        methods.add_function("remove_dir", |lua, path: String| {
+            let path = permissions::current(lua).check_path(&path)?;
             let r = remove_dir_all(path);
             Ok(r.is_ok())
         });
-        methods.add_function("file_get_content", |_, path: String| {
-            let path = PathBuf::from(path);
-            let mut file = std::fs::File::open(path)?;
-            let mut buffer = String::new();
-            file.read_to_string(&mut buffer)?;
-            Ok(buffer)
+        methods.add_async_function("file_get_content", |lua, path: String| {
+            let path = permissions::current(lua).check_path(&path);
+            async move { Ok::<_, mlua::Error>(tokio::fs::read_to_string(path?).await?) }
         });
-        methods.add_function("file_set_content", |_, args: (String, String)| {
+        methods.add_async_function("file_set_content", |lua, args: (String, String)| {
             let path = args.0;
             let content = args.1;
-            let path = PathBuf::from(path);
+            let path = permissions::current(lua).check_path(&path);
 
-            let file = std::fs::File::create(path);
-            if file.is_err() {
-                return Ok(false);
-            }
-
-            if file.unwrap().write_all(content.as_bytes()).is_err() {
+            async move { Ok::<_, mlua::Error>(tokio::fs::write(path?, content).await.is_ok()) }
+        });
+        methods.add_function("list_files", |lua, args: (String, Option<String>)| {
+            let dir = permissions::current(lua).check_path(&args.0)?;
+            let pattern = match args.1 {
+                Some(pattern) => Some(
+                    Glob::new(&pattern)
+                        .map_err(mlua::Error::external)?
+                        .compile_matcher(),
+                ),
+                None => None,
+            };
+            let mut files: Vec<String> = WalkDir::new(&dir)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .filter(|entry| match &pattern {
+                    Some(pattern) => pattern.is_match(entry.path().strip_prefix(&dir).unwrap()),
+                    None => true,
+                })
+                .map(|entry| entry.path().to_string_lossy().to_string())
+                .collect();
+            files.sort();
+            Ok(files)
+        });
+        methods.add_function("unzip_file", |lua, args: (String, String)| {
+            let sandbox = permissions::current(lua);
+            let file = sandbox.check_path(&args.0)?;
+            let target = sandbox.check_path(&args.1)?;
+            if !zip_is_contained(&file) {
+                log::warn!(
+                    "Refusing to unzip `{}`, it has links or paths outside of it.",
+                    args.0
+                );
                 return Ok(false);
             }
-
-            Ok(true)
-        });
-        methods.add_function("unzip_file", |_, args: (String, String)| {
-            let file = PathBuf::from(args.0);
-            let target = PathBuf::from(args.1);
             let res = extract_zip(&file, &target);
             if let Err(_) = res {
                 return Ok(false);
             }
             Ok(true)
         });
-        methods.add_function("untar_gz_file", |_, args: (String, String)| {
-            let file = PathBuf::from(args.0);
-            let target = PathBuf::from(args.1);
+        methods.add_function("untar_gz_file", |lua, args: (String, String)| {
+            let sandbox = permissions::current(lua);
+            let file = sandbox.check_path(&args.0)?;
+            let target = sandbox.check_path(&args.1)?;
 
             let tar_gz = if let Ok(v) = File::open(file) {
                 v
@@ -75,11 +97,47 @@ impl UserData for PluginFileSystem {
 
             let tar = GzDecoder::new(tar_gz);
             let mut archive = Archive::new(tar);
-            if archive.unpack(&target).is_err() {
+            let Ok(entries) = archive.entries() else {
                 return Ok(false);
+            };
+            let mut complete = true;
+            for entry in entries {
+                let Ok(mut entry) = entry else {
+                    return Ok(false);
+                };
+                // links could point anywhere, and the sandbox would follow them later
+                let kind = entry.header().entry_type();
+                if kind.is_symlink() || kind.is_hard_link() {
+                    log::warn!(
+                        "Skipping the link `{}` in `{}`.",
+                        entry.path()?.display(),
+                        args.0
+                    );
+                    complete = false;
+                    continue;
+                }
+                if !matches!(entry.unpack_in(&target), Ok(true)) {
+                    complete = false;
+                }
             }
 
-            Ok(true)
+            Ok(complete)
         });
     }
 }
+
+/// Whether every entry of the zip file is a plain file or directory inside of it
+fn zip_is_contained(file: &Path) -> bool {
+    let Some(mut zip) = File::open(file).ok().and_then(|f| ZipArchive::new(f).ok()) else {
+        return false;
+    };
+    (0..zip.len()).all(|i| match zip.by_index(i) {
+        Ok(entry) => {
+            let is_link = entry
+                .unix_mode()
+                .map_or(false, |mode| mode & 0o170000 == 0o120000);
+            entry.enclosed_name().is_some() && !is_link
+        }
+        Err(_) => false,
+    })
+}
@@ -0,0 +1,36 @@
+use mlua::UserData;
+use sha2::{Digest, Sha256};
+
+use crate::plugin::permissions;
+
+pub struct PluginHash;
+impl UserData for PluginHash {
+    fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_function("sha256", |_, content: mlua::String| {
+            Ok(format!("{:x}", Sha256::digest(content.as_bytes())))
+        });
+        methods.add_async_function("sha256_file", |lua, path: String| {
+            let path = permissions::current(lua).check_path(&path);
+            async move {
+                let content = tokio::fs::read(path?).await?;
+                Ok::<_, mlua::Error>(format!("{:x}", Sha256::digest(content)))
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sha256() {
+        let lua = mlua::Lua::new();
+        lua.globals().set("hash", PluginHash).unwrap();
+        let digest: String = lua.load(r#"return hash.sha256("abc")"#).eval().unwrap();
+        assert_eq!(
+            digest,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use mlua::{LuaSerdeExt, UserData};
+
+pub struct PluginJson;
+impl UserData for PluginJson {
+    fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_function("encode", |lua, args: (mlua::Value, Option<bool>)| {
+            let value: serde_json::Value = lua.from_value(args.0)?;
+            let text = if args.1.unwrap_or(false) {
+                serde_json::to_string_pretty(&value)
+            } else {
+                serde_json::to_string(&value)
+            };
+            text.map_err(mlua::Error::external)
+        });
+        methods.add_function("decode", |lua, text: String| {
+            let value: serde_json::Value =
+                serde_json::from_str(&text).map_err(mlua::Error::external)?;
+            lua.to_value(&value)
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let lua = mlua::Lua::new();
+        lua.globals().set("json", PluginJson).unwrap();
+        let text: String = lua
+            .load(
+                r#"return json.encode(json.decode('{"name":"app","tags":["a","b"],"port":8080}'))"#,
+            )
+            .eval()
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({ "name": "app", "tags": ["a", "b"], "port": 8080 })
+        );
+        let pretty: String = lua
+            .load(r#"return json.encode({ a = 1 }, true)"#)
+            .eval()
+            .unwrap();
+        assert_eq!(pretty, "{\n  \"a\": 1\n}");
+        assert!(lua.load(r#"return json.decode("{")"#).exec().is_err());
+    }
+}
@@ -1,12 +1,18 @@
-use mlua::{FromLua, Function, ToLua};
+use mlua::{FromLua, Function, Table, ToLua};
+
+use super::permissions::PluginPermissions;
 
 pub mod command;
 pub mod dirs;
+pub mod env;
 pub mod fs;
+pub mod hash;
+pub mod json;
 pub mod log;
 pub mod network;
 pub mod os;
 pub mod path;
+pub mod toml;
 
 #[derive(Debug, Clone)]
 pub struct PluginInfo<'lua> {
@@ -14,6 +20,7 @@ pub struct PluginInfo<'lua> {
     pub repository: String,
     pub author: String,
     pub version: String,
+    pub permissions: PluginPermissions,
 
     pub inner: PluginInner,
 
@@ -29,6 +36,7 @@ impl<'lua> FromLua<'lua> for PluginInfo<'lua> {
             repository: String::default(),
             author: String::default(),
             version: String::from("0.1.0"),
+            permissions: Default::default(),
 
             inner: Default::default(),
 
@@ -49,6 +57,9 @@ impl<'lua> FromLua<'lua> for PluginInfo<'lua> {
             if let Ok(v) = tab.get::<_, String>("version") {
                 res.version = v;
             }
+            if let Ok(v) = tab.get::<_, PluginPermissions>("permissions") {
+                res.permissions = v;
+            }
 
             if let Ok(v) = tab.get::<_, PluginInner>("inner") {
                 res.inner = v;
@@ -79,6 +90,7 @@ impl<'lua> ToLua<'lua> for PluginInfo<'lua> {
         res.set("repository", self.repository.to_string())?;
         res.set("author", self.author.to_string())?;
         res.set("version", self.version.to_string())?;
+        res.set("permissions", self.permissions)?;
 
         res.set("inner", self.inner)?;
 
@@ -132,14 +144,17 @@ impl<'lua> ToLua<'lua> for PluginInner {
 pub struct PluginBuildInfo<'lua> {
     pub on_start: Option<Function<'lua>>,
     pub on_finish: Option<Function<'lua>>,
+
+    pub after_cargo: Option<Function<'lua>>,
+    pub after_bindgen: Option<Function<'lua>>,
+    pub after_wasm_opt: Option<Function<'lua>>,
+    pub transform_asset: Option<Function<'lua>>,
+    pub transform_html: Option<Function<'lua>>,
 }
 
 impl<'lua> FromLua<'lua> for PluginBuildInfo<'lua> {
     fn from_lua(lua_value: mlua::Value<'lua>, _lua: &'lua mlua::Lua) -> mlua::Result<Self> {
-        let mut res = Self {
-            on_start: None,
-            on_finish: None,
-        };
+        let mut res = Self::default();
 
         if let mlua::Value::Table(t) = lua_value {
             if let Ok(v) = t.get::<_, Function>("on_start") {
@@ -148,6 +163,21 @@ impl<'lua> FromLua<'lua> for PluginBuildInfo<'lua> {
             if let Ok(v) = t.get::<_, Function>("on_finish") {
                 res.on_finish = Some(v);
             }
+            if let Ok(v) = t.get::<_, Function>("after_cargo") {
+                res.after_cargo = Some(v);
+            }
+            if let Ok(v) = t.get::<_, Function>("after_bindgen") {
+                res.after_bindgen = Some(v);
+            }
+            if let Ok(v) = t.get::<_, Function>("after_wasm_opt") {
+                res.after_wasm_opt = Some(v);
+            }
+            if let Ok(v) = t.get::<_, Function>("transform_asset") {
+                res.transform_asset = Some(v);
+            }
+            if let Ok(v) = t.get::<_, Function>("transform_html") {
+                res.transform_html = Some(v);
+            }
         }
 
         Ok(res)
@@ -166,6 +196,26 @@ impl<'lua> ToLua<'lua> for PluginBuildInfo<'lua> {
             res.set("on_finish", v)?;
         }
 
+        if let Some(v) = self.after_cargo {
+            res.set("after_cargo", v)?;
+        }
+
+        if let Some(v) = self.after_bindgen {
+            res.set("after_bindgen", v)?;
+        }
+
+        if let Some(v) = self.after_wasm_opt {
+            res.set("after_wasm_opt", v)?;
+        }
+
+        if let Some(v) = self.transform_asset {
+            res.set("transform_asset", v)?;
+        }
+
+        if let Some(v) = self.transform_html {
+            res.set("transform_html", v)?;
+        }
+
         Ok(mlua::Value::Table(res))
     }
 }
@@ -178,6 +228,13 @@ pub struct PluginServeInfo<'lua> {
     pub on_interval: Option<Function<'lua>>,
     pub on_rebuild: Option<Function<'lua>>,
     pub on_shutdown: Option<Function<'lua>>,
+
+    /// Handlers of extra dev server routes, by path
+    pub routes: Option<Table<'lua>>,
+    /// Transforms every response of the dev server
+    pub middleware: Option<Function<'lua>>,
+    /// Functions run in the background while serving, by name
+    pub tasks: Option<Table<'lua>>,
 }
 
 impl<'lua> FromLua<'lua> for PluginServeInfo<'lua> {
@@ -200,6 +257,15 @@ impl<'lua> FromLua<'lua> for PluginServeInfo<'lua> {
             if let Ok(v) = tab.get::<_, Function>("on_shutdown") {
                 res.on_shutdown = Some(v);
             }
+            if let Ok(v) = tab.get::<_, Table>("routes") {
+                res.routes = Some(v);
+            }
+            if let Ok(v) = tab.get::<_, Function>("middleware") {
+                res.middleware = Some(v);
+            }
+            if let Ok(v) = tab.get::<_, Table>("tasks") {
+                res.tasks = Some(v);
+            }
         }
 
         Ok(res)
@@ -228,6 +294,18 @@ impl<'lua> ToLua<'lua> for PluginServeInfo<'lua> {
             res.set("on_shutdown", v)?;
         }
 
+        if let Some(v) = self.routes {
+            res.set("routes", v)?;
+        }
+
+        if let Some(v) = self.middleware {
+            res.set("middleware", v)?;
+        }
+
+        if let Some(v) = self.tasks {
+            res.set("tasks", v)?;
+        }
+
         Ok(mlua::Value::Table(res))
     }
 }
@@ -1,27 +1,139 @@
-use std::{io::Cursor, path::PathBuf};
+use std::{collections::BTreeMap, sync::Arc};
 
-use mlua::UserData;
+use mlua::{FromLua, ToLua, UserData};
+
+use crate::plugin::permissions::{self, Sandbox};
+
+/// How many redirects a request may follow, like the default of reqwest
+const MAX_REDIRECTS: usize = 10;
+
+/// The options of `request`
+struct HttpRequest {
+    url: String,
+    method: reqwest::Method,
+    headers: BTreeMap<String, String>,
+    body: Option<Vec<u8>>,
+}
+
+impl<'lua> FromLua<'lua> for HttpRequest {
+    fn from_lua(lua_value: mlua::Value<'lua>, _lua: &'lua mlua::Lua) -> mlua::Result<Self> {
+        let mlua::Value::Table(tab) = lua_value else {
+            return Err(mlua::Error::FromLuaConversionError {
+                from: lua_value.type_name(),
+                to: "request",
+                message: Some("expected a table".into()),
+            });
+        };
+        let method =
+            match tab.get::<_, Option<String>>("method")? {
+                Some(method) => reqwest::Method::from_bytes(method.to_uppercase().as_bytes())
+                    .map_err(|_| mlua::Error::FromLuaConversionError {
+                        from: "string",
+                        to: "method",
+                        message: Some(format!("invalid HTTP method `{method}`")),
+                    })?,
+                None => reqwest::Method::GET,
+            };
+        Ok(Self {
+            url: tab.get("url")?,
+            method,
+            headers: tab.get::<_, Option<_>>("headers")?.unwrap_or_default(),
+            body: tab
+                .get::<_, Option<mlua::String>>("body")?
+                .map(|body| body.as_bytes().to_vec()),
+        })
+    }
+}
+
+struct HttpResponse {
+    status: u16,
+    headers: BTreeMap<String, String>,
+    body: Vec<u8>,
+}
+
+impl<'lua> ToLua<'lua> for HttpResponse {
+    fn to_lua(self, lua: &'lua mlua::Lua) -> mlua::Result<mlua::Value<'lua>> {
+        let res = lua.create_table()?;
+        res.set("status", self.status)?;
+        res.set("headers", self.headers)?;
+        res.set("body", lua.create_string(&self.body)?)?;
+        Ok(mlua::Value::Table(res))
+    }
+}
+
+/// A client that only follows redirects to hosts the plugin may reach
+fn client(sandbox: Arc<Sandbox>) -> reqwest::Result<reqwest::Client> {
+    let policy = reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else if let Err(e) = sandbox.check_url(attempt.url().as_str()) {
+            attempt.error(e.to_string())
+        } else {
+            attempt.follow()
+        }
+    });
+    reqwest::Client::builder().redirect(policy).build()
+}
+
+async fn send(sandbox: Arc<Sandbox>, request: HttpRequest) -> reqwest::Result<HttpResponse> {
+    let mut builder = client(sandbox)?.request(request.method, &request.url);
+    for (name, value) in &request.headers {
+        builder = builder.header(name, value);
+    }
+    if let Some(body) = request.body {
+        builder = builder.body(body);
+    }
+
+    let response = builder.send().await?;
+    let status = response.status().as_u16();
+    // header values aren't always valid UTF-8, but plugins expect strings
+    let headers = response
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+            (name.to_string(), value)
+        })
+        .collect();
+    let body = response.bytes().await?.to_vec();
+    Ok(HttpResponse {
+        status,
+        headers,
+        body,
+    })
+}
 
 pub struct PluginNetwork;
 impl UserData for PluginNetwork {
     fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
-        methods.add_function("download_file", |_, args: (String, String)| {
+        methods.add_async_function("download_file", |lua, args: (String, String)| {
             let url = args.0;
-            let path = args.1;
+            let sandbox = permissions::current(lua);
+            let allowed = sandbox
+                .check_url(&url)
+                .and_then(|_| sandbox.check_path(&args.1));
 
-            let resp = reqwest::blocking::get(url);
-            if let Ok(resp) = resp {
-                let mut content = Cursor::new(resp.bytes().unwrap());
-                let file = std::fs::File::create(PathBuf::from(path));
-                if file.is_err() {
+            async move {
+                let path = allowed?;
+                let Ok(client) = client(sandbox) else {
+                    return Ok(false);
+                };
+                let Ok(resp) = client.get(url).send().await else {
+                    return Ok(false);
+                };
+                let Ok(content) = resp.bytes().await else {
                     return Ok(false);
-                }
-                let mut file = file.unwrap();
-                let res = std::io::copy(&mut content, &mut file);
-                return Ok(res.is_ok());
+                };
+                Ok::<_, mlua::Error>(tokio::fs::write(path, content).await.is_ok())
+            }
+        });
+        methods.add_async_function("request", |lua, request: HttpRequest| {
+            let sandbox = permissions::current(lua);
+            let allowed = sandbox.check_url(&request.url);
+            async move {
+                allowed?;
+                send(sandbox, request).await.map_err(mlua::Error::external)
             }
-
-            Ok(false)
         });
     }
 }
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use mlua::UserData;
 
 pub struct PluginOS;
@@ -14,5 +16,9 @@ impl UserData for PluginOS {
                 panic!("unsupported platformm");
             }
         });
+        methods.add_async_function("sleep", |_, millis: u64| async move {
+            tokio::time::sleep(Duration::from_millis(millis)).await;
+            Ok::<_, mlua::Error>(())
+        });
     }
 }
@@ -0,0 +1,40 @@
+use mlua::{LuaSerdeExt, UserData};
+
+pub struct PluginToml;
+impl UserData for PluginToml {
+    fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_function("encode", |lua, value: mlua::Value| {
+            let value: ::toml::Value = lua.from_value(value)?;
+            ::toml::to_string_pretty(&value).map_err(mlua::Error::external)
+        });
+        methods.add_function("decode", |lua, text: String| {
+            let value: ::toml::Value = ::toml::from_str(&text).map_err(mlua::Error::external)?;
+            lua.to_value(&value)
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let lua = mlua::Lua::new();
+        lua.globals().set("toml", PluginToml).unwrap();
+        let text: String = lua
+            .load(
+                r#"
+                local config = toml.decode('[application]\nname = "app"\nport = 8080')
+                config.application.name = "renamed"
+                return toml.encode(config)
+                "#,
+            )
+            .eval()
+            .unwrap();
+        let value: ::toml::Value = ::toml::from_str(&text).unwrap();
+        assert_eq!(value["application"]["name"].as_str(), Some("renamed"));
+        assert_eq!(value["application"]["port"].as_integer(), Some(8080));
+        assert!(lua.load(r#"return toml.decode("[broken")"#).exec().is_err());
+    }
+}
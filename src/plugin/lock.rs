@@ -0,0 +1,256 @@
+//! Project plugins declared in `[plugin.dependencies]` and pinned in `Dioxus.lock`
+//!
+//! Locked plugins are cached under `app_path()/plugin-cache/<name>/<checksum>`, so every project
+//! locking the same files shares one copy and nothing depends on what happens to be installed
+//! globally.
+use super::{
+    permissions::Approval,
+    registry::{install_into, staging_dir, InstalledPlugin, PluginSource},
+    types::PluginDependency,
+};
+use crate::tools::app_path;
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+pub const LOCK_FILE: &str = "Dioxus.lock";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PluginLock {
+    #[serde(default)]
+    pub plugin: Vec<InstalledPlugin>,
+    /// The approved permissions, by [`approval_key`](super::permissions::approval_key)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub permissions: BTreeMap<String, Approval>,
+}
+
+impl PluginLock {
+    pub fn load(crate_dir: &Path) -> anyhow::Result<Self> {
+        let file = crate_dir.join(LOCK_FILE);
+        if !file.is_file() {
+            return Ok(Self::default());
+        }
+        toml::from_str(&std::fs::read_to_string(file)?)
+            .with_context(|| format!("Invalid `{LOCK_FILE}`"))
+    }
+
+    pub fn save(&self, crate_dir: &Path) -> anyhow::Result<()> {
+        let text = format!(
+            "# This file is generated by the Dioxus CLI, don't edit it by hand.\n\n{}",
+            toml::to_string_pretty(self)?
+        );
+        std::fs::write(crate_dir.join(LOCK_FILE), text)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&InstalledPlugin> {
+        self.plugin.iter().find(|plugin| plugin.name == name)
+    }
+}
+
+/// Resolve `[plugin.dependencies]` against the lockfile and return the plugin directories to load
+///
+/// Locked plugins are installed from the locked commit if they aren't cached yet. New
+/// dependencies, and dependencies whose requirement no longer matches the locked version, are
+/// resolved to the highest matching version and written to the lockfile. Path dependencies are
+/// loaded in place and aren't locked.
+pub async fn load_dependencies(
+    crate_dir: &Path,
+    dependencies: &BTreeMap<String, PluginDependency>,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let lock = PluginLock::load(crate_dir)?;
+    let mut locked = vec![];
+    let mut dirs = vec![];
+    let mut changed = false;
+
+    for (name, dependency) in dependencies {
+        let source = match dependency {
+            PluginDependency::Path(path) => {
+                dirs.push(crate_dir.join(path));
+                continue;
+            }
+            PluginDependency::Index { version } => PluginSource::Index {
+                name: name.clone(),
+                version: Some(version.clone()),
+            },
+            PluginDependency::Git { url, rev } => PluginSource::Git {
+                url: url.clone(),
+                rev: rev.clone(),
+            },
+        };
+
+        let entry = match lock.get(name) {
+            Some(entry) if satisfies(entry, dependency) => {
+                if !cache_dir(entry).is_dir() {
+                    install_cached(&entry.exact_source(), name, Some(entry)).await?;
+                }
+                entry.clone()
+            }
+            _ => {
+                changed = true;
+                install_cached(&source, name, None).await?
+            }
+        };
+        dirs.push(cache_dir(&entry));
+        locked.push(entry);
+    }
+
+    if changed || locked.len() != lock.plugin.len() {
+        PluginLock {
+            plugin: locked,
+            permissions: lock.permissions,
+        }
+        .save(crate_dir)?;
+    }
+    Ok(dirs)
+}
+
+/// Whether the locked plugin still fulfills `dependency`
+fn satisfies(locked: &InstalledPlugin, dependency: &PluginDependency) -> bool {
+    match dependency {
+        PluginDependency::Index { version } => {
+            let req = semver::VersionReq::parse(version);
+            let locked_version = locked.version.as_deref().map(semver::Version::parse);
+            match (req, locked_version) {
+                (Ok(req), Some(Ok(locked_version))) => {
+                    locked.source == "index" && req.matches(&locked_version)
+                }
+                _ => false,
+            }
+        }
+        PluginDependency::Git { url, rev } => {
+            locked.source == "git" && locked.location.as_ref() == Some(url) && locked.rev == *rev
+        }
+        PluginDependency::Path(_) => false,
+    }
+}
+
+/// Install `source` into the plugin cache, checking it against `locked` if it's a locked plugin
+async fn install_cached(
+    source: &PluginSource,
+    name: &str,
+    locked: Option<&InstalledPlugin>,
+) -> anyhow::Result<InstalledPlugin> {
+    // the cache directory depends on the checksum, which is only known after fetching. The
+    // staging directory is unique, so projects resolving at the same time don't collide.
+    let staging = staging_dir(&app_path().join("plugin-cache").join(name), ".resolving");
+    let result = stage_plugin(source, name, locked, &staging).await;
+    let _ = std::fs::remove_dir_all(&staging);
+    result
+}
+
+async fn stage_plugin(
+    source: &PluginSource,
+    name: &str,
+    locked: Option<&InstalledPlugin>,
+    staging: &Path,
+) -> anyhow::Result<InstalledPlugin> {
+    let fetched = staging.join(name);
+    let installed = install_into(source, name, None, &fetched).await?;
+    if let Some(locked) = locked {
+        check_checksum(locked, &installed)?;
+    }
+    let dir = cache_dir(&installed);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    std::fs::rename(&fetched, &dir)?;
+    Ok(installed)
+}
+
+/// A plugin installed from its locked source must be exactly the locked plugin
+fn check_checksum(locked: &InstalledPlugin, installed: &InstalledPlugin) -> anyhow::Result<()> {
+    if installed.checksum != locked.checksum {
+        return Err(anyhow!(
+            "Plugin `{}` doesn't match `{LOCK_FILE}`: expected {}, got {}.",
+            locked.name,
+            locked.checksum,
+            installed.checksum
+        ));
+    }
+    Ok(())
+}
+
+fn cache_dir(locked: &InstalledPlugin) -> PathBuf {
+    let hash = locked.checksum.trim_start_matches("sha256:");
+    app_path()
+        .join("plugin-cache")
+        .join(&locked.name)
+        .join(&hash[..hash.len().min(16)])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn locked(source: &str, version: Option<&str>, location: Option<&str>) -> InstalledPlugin {
+        InstalledPlugin {
+            name: "tailwind".into(),
+            source: source.into(),
+            location: location.map(Into::into),
+            version: version.map(Into::into),
+            rev: None,
+            commit: Some("0123abcd".into()),
+            checksum: "sha256:0123456789abcdef0123".into(),
+        }
+    }
+
+    #[test]
+    fn index_requirement() {
+        let index = |version: &str| PluginDependency::Index {
+            version: version.into(),
+        };
+        let entry = locked("index", Some("0.2.3"), None);
+        assert!(satisfies(&entry, &index("0.2")));
+        assert!(satisfies(&entry, &index(">=0.2.1, <0.3")));
+        assert!(!satisfies(&entry, &index("0.3")));
+        assert!(!satisfies(&entry, &index("not a version")));
+        assert!(!satisfies(&locked("index", None, None), &index("0.2")));
+        // the dependency moved to the index, the plugin has to be resolved again
+        let git = locked(
+            "git",
+            Some("0.2.3"),
+            Some("https://example.com/tailwind.git"),
+        );
+        assert!(!satisfies(&git, &index("0.2")));
+    }
+
+    #[test]
+    fn git_source() {
+        let url = "https://example.com/tailwind.git";
+        let git = |url: &str, rev: Option<&str>| PluginDependency::Git {
+            url: url.into(),
+            rev: rev.map(Into::into),
+        };
+        let mut entry = locked("git", None, Some(url));
+        assert!(satisfies(&entry, &git(url, None)));
+        assert!(!satisfies(
+            &entry,
+            &git("https://example.com/fork.git", None)
+        ));
+        assert!(!satisfies(&entry, &git(url, Some("v2"))));
+
+        entry.rev = Some("v2".into());
+        assert!(satisfies(&entry, &git(url, Some("v2"))));
+        assert!(!satisfies(&entry, &git(url, None)));
+        assert!(!satisfies(
+            &entry,
+            &PluginDependency::Path("../tailwind".into())
+        ));
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let entry = locked("index", Some("0.2.3"), None);
+        assert!(check_checksum(&entry, &entry.clone()).is_ok());
+
+        let mut tampered = entry.clone();
+        tampered.checksum = "sha256:fedcba9876543210fedc".into();
+        let err = check_checksum(&entry, &tampered).unwrap_err().to_string();
+        assert!(err.contains("doesn't match `Dioxus.lock`"));
+        assert!(err.contains("sha256:fedcba9876543210fedc"));
+    }
+}
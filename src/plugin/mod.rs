@@ -1,72 +1,261 @@
 use std::{
+    collections::HashMap,
     io::{Read, Write},
-    path::PathBuf,
-    sync::Mutex,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
-use mlua::{Lua, Table};
+use mlua::{FromLuaMulti, Function, HookTriggers, Lua, MultiValue, Table, ToLuaMulti, Value};
 use serde_json::json;
 
-use crate::{
-    tools::{app_path, clone_repo},
-    CrateConfig,
-};
+use crate::{CrateConfig, DioxusConfig};
 
 use self::{
+    commands::PluginCommand,
     interface::{
-        command::PluginCommander, dirs::PluginDirs, fs::PluginFileSystem, log::PluginLogger,
-        network::PluginNetwork, os::PluginOS, path::PluginPath, PluginInfo,
+        command::PluginCommander, dirs::PluginDirs, env::PluginEnv, fs::PluginFileSystem,
+        hash::PluginHash, json::PluginJson, log::PluginLogger, network::PluginNetwork,
+        os::PluginOS, path::PluginPath, toml::PluginToml, PluginInfo, PluginInner,
     },
+    permissions::{Approval, ApprovalStore, PluginPermissions, Sandbox},
+    routes::{PluginRequest, PluginResponse},
     types::PluginConfig,
 };
 
+pub mod commands;
 pub mod interface;
+pub mod lock;
+pub mod permissions;
+pub mod registry;
+pub mod routes;
+pub mod runtime;
+pub mod testing;
 mod types;
 
+/// How often the instruction limit is checked
+const HOOK_INTERVAL: u32 = 10_000;
+
+/// How long a dev server route or middleware may wait, e.g. for a request of its own
+///
+/// A plugin stays locked while it handles a request, so a handler that never returns would
+/// block every later request and hook of its plugin.
+const SERVE_TIMEOUT: Duration = Duration::from_secs(30);
+
 lazy_static::lazy_static! {
-    static ref LUA: Mutex<Lua> = Mutex::new(Lua::new());
+    /// Every loaded plugin, in load order
+    static ref PLUGINS: Mutex<Vec<Arc<Mutex<LuaPlugin>>>> = Mutex::new(vec![]);
 }
 
-pub struct PluginManager;
-
-impl PluginManager {
-    pub fn init(config: toml::Value) -> anyhow::Result<()> {
-        let config = PluginConfig::from_toml_value(config);
+/// A loaded plugin with its own Lua state, so plugins can't see or break each other
+struct LuaPlugin {
+    lua: Lua,
+    name: String,
+    from_loader: bool,
+    instruction_limit: u64,
+    commands: Vec<PluginCommand>,
+    /// To start background tasks in fresh states of the plugin
+    plugin_dir: PathBuf,
+    config: PluginConfig,
+}
 
-        if !config.available {
-            return Ok(());
+/// The instructions left for the current call into a plugin
+struct InstructionBudget(u64);
+
+impl LuaPlugin {
+    /// A fresh Lua state with the plugin api for the plugin in `plugin_dir`
+    fn create_state(
+        config: &PluginConfig,
+        library_dir: &Path,
+        plugin_dir: &Path,
+        from_loader: bool,
+    ) -> mlua::Result<Lua> {
+        let lua = Lua::new();
+        permissions::restrict_globals(&lua)?;
+
+        let api = lua.create_table()?;
+        api.set("log", PluginLogger)?;
+        api.set("command", PluginCommander)?;
+        api.set("network", PluginNetwork)?;
+        api.set("dirs", PluginDirs)?;
+        api.set("fs", PluginFileSystem)?;
+        api.set("path", PluginPath)?;
+        api.set("os", PluginOS)?;
+        api.set("env", PluginEnv)?;
+        api.set("json", PluginJson)?;
+        api.set("toml", PluginToml)?;
+        api.set("hash", PluginHash)?;
+
+        lua.globals().set("plugin_lib", api)?;
+        // the `plugin` and `manager` modules of the old plugin library are built in
+        lua.load(
+            r#"
+            package.preload["plugin"] = function () return plugin_lib end
+            package.preload["manager"] = function () return { build = {}, serve = {} } end
+            "#,
+        )
+        .exec()?;
+        lua.globals()
+            .set("library_dir", library_dir.to_str().unwrap())?;
+        lua.globals().set("config_info", config.clone())?;
+        lua.globals()
+            .set("_temp_plugin_dir", plugin_dir.to_str().unwrap())?;
+        lua.globals().set("_temp_from_loader", from_loader)?;
+
+        if config.instruction_limit > 0 {
+            let triggers = HookTriggers {
+                every_nth_instruction: Some(HOOK_INTERVAL),
+                ..Default::default()
+            };
+            lua.set_hook(triggers, |lua, _| {
+                let Some(mut budget) = lua.app_data_mut::<InstructionBudget>() else {
+                    return Ok(());
+                };
+                budget.0 = budget.0.saturating_sub(HOOK_INTERVAL as u64);
+                if budget.0 == 0 {
+                    return Err(mlua::Error::RuntimeError(
+                        "instruction limit exceeded, the plugin may be stuck in a loop".into(),
+                    ));
+                }
+                Ok(())
+            })?;
         }
 
-        let lua = LUA.lock().unwrap();
+        // the manifest itself runs without any permissions
+        let sandbox = Sandbox::new(&PluginPermissions::default(), plugin_dir, None, None);
+        permissions::apply(&lua, sandbox);
+
+        Ok(lua)
+    }
+
+    /// Give the next call into the plugin the full instruction limit
+    fn reset_budget(lua: &Lua, instruction_limit: u64) {
+        lua.set_app_data(InstructionBudget(instruction_limit));
+    }
 
-        let manager = lua.create_table().unwrap();
-        let name_index = lua.create_table().unwrap();
+    fn info(&self) -> mlua::Result<PluginInfo> {
+        self.lua.globals().get::<_, PluginInfo>("manager")
+    }
 
-        let plugin_dir = Self::init_plugin_dir();
+    /// Call the hook picked by `select` with the arguments built by `args`, `None` if the plugin
+    /// doesn't have it
+    fn call_hook<A, R>(&self, select: HookSelector, args: A) -> mlua::Result<Option<R>>
+    where
+        A: for<'lua> Fn(&'lua Lua) -> mlua::Result<Table<'lua>>,
+        R: for<'lua> FromLuaMulti<'lua>,
+    {
+        let Some(func) = select(self.info()?) else {
+            return Ok(None);
+        };
+        Self::reset_budget(&self.lua, self.instruction_limit);
+        runtime::block_on(func.call_async::<Table, R>(args(&self.lua)?)).map(Some)
+    }
+}
 
-        let api = lua.create_table().unwrap();
+/// Picks a hook out of the manifest of a plugin
+type HookSelector = for<'lua> fn(PluginInfo<'lua>) -> Option<Function<'lua>>;
+
+/// What a build hook returned
+enum HookOutput {
+    /// `nil` or `true`
+    Continue,
+    /// `false` and an optional message, which fails the build
+    Fail(Option<String>),
+    /// A string replacing the content given to a transform hook
+    Replace(Vec<u8>),
+}
 
-        api.set("log", PluginLogger).unwrap();
-        api.set("command", PluginCommander).unwrap();
-        api.set("network", PluginNetwork).unwrap();
-        api.set("dirs", PluginDirs).unwrap();
-        api.set("fs", PluginFileSystem).unwrap();
-        api.set("path", PluginPath).unwrap();
-        api.set("os", PluginOS).unwrap();
+impl<'lua> FromLuaMulti<'lua> for HookOutput {
+    fn from_lua_multi(values: MultiValue<'lua>, _lua: &'lua Lua) -> mlua::Result<Self> {
+        let mut values = values.into_iter();
+        Ok(match values.next() {
+            None | Some(Value::Nil) | Some(Value::Boolean(true)) => Self::Continue,
+            Some(Value::Boolean(false)) => Self::Fail(match values.next() {
+                Some(Value::String(message)) => Some(message.to_str()?.to_string()),
+                _ => None,
+            }),
+            Some(Value::String(content)) => Self::Replace(content.as_bytes().to_vec()),
+            Some(other) => {
+                return Err(mlua::Error::FromLuaConversionError {
+                    from: other.type_name(),
+                    to: "hook result",
+                    message: Some("expected nil, a boolean or a string".into()),
+                })
+            }
+        })
+    }
+}
 
-        lua.globals().set("plugin_lib", api).unwrap();
-        lua.globals()
-            .set("library_dir", plugin_dir.to_str().unwrap())
-            .unwrap();
-        lua.globals().set("config_info", config.clone())?;
+/// The arguments every build hook gets
+fn build_args<'lua>(
+    lua: &'lua Lua,
+    crate_config: &CrateConfig,
+    platform: &str,
+) -> mlua::Result<Table<'lua>> {
+    let args = lua.create_table()?;
+    args.set("name", crate_config.dioxus_config.application.name.clone())?;
+    args.set("platform", platform)?;
+    args.set("out_dir", crate_config.out_dir.to_str().unwrap())?;
+    args.set("asset_dir", crate_config.asset_dir.to_str().unwrap())?;
+    Ok(args)
+}
+
+/// Call a `serve` handler, cancelling it after [`SERVE_TIMEOUT`]
+fn call_with_timeout<'lua, A, R>(func: Function<'lua>, args: A) -> mlua::Result<R>
+where
+    A: ToLuaMulti<'lua>,
+    R: FromLuaMulti<'lua>,
+{
+    let call = tokio::time::timeout(SERVE_TIMEOUT, func.call_async::<A, R>(args));
+    runtime::block_on(call).unwrap_or_else(|_| {
+        Err(mlua::Error::RuntimeError(format!(
+            "timed out after {}s",
+            SERVE_TIMEOUT.as_secs()
+        )))
+    })
+}
+
+pub struct PluginManager;
 
-        let mut index: u32 = 1;
-        let dirs = std::fs::read_dir(&plugin_dir)?;
+impl PluginManager {
+    pub async fn init(dioxus_config: &DioxusConfig) -> anyhow::Result<()> {
+        let config = PluginConfig::from_toml_value(dioxus_config.plugin.clone());
+        runtime::init();
 
-        let mut path_list = dirs
-            .filter(|v| v.is_ok())
-            .map(|v| (v.unwrap().path(), false))
-            .collect::<Vec<(PathBuf, bool)>>();
+        if !config.available {
+            return Ok(());
+        }
+
+        let crate_dir = crate::cargo::crate_root().ok();
+        let out_dir = crate_dir.as_ref().map(|dir| {
+            let out_dir = dioxus_config.application.out_dir.clone();
+            dir.join(out_dir.unwrap_or_else(|| PathBuf::from("dist")))
+        });
+
+        // projects declaring their plugins load exactly those, instead of every installed one
+        let (project_plugins, approvals) = match &crate_dir {
+            Some(crate_dir) if !config.dependencies.is_empty() => (
+                Some(lock::load_dependencies(crate_dir, &config.dependencies).await?),
+                ApprovalStore::Project(crate_dir.clone()),
+            ),
+            _ if !config.dependencies.is_empty() => {
+                return Err(anyhow::anyhow!(
+                    "`[plugin.dependencies]` can only be used in a crate."
+                ))
+            }
+            _ => (None, ApprovalStore::Global),
+        };
+
+        let library_dir = registry::plugins_dir();
+
+        let mut path_list = match project_plugins {
+            Some(dirs) => dirs.into_iter().map(|dir| (dir, false)).collect(),
+            None => std::fs::read_dir(&library_dir)?
+                .filter(|v| v.is_ok())
+                .map(|v| (v.unwrap().path(), false))
+                .collect::<Vec<(PathBuf, bool)>>(),
+        };
         for i in &config.loader {
             let path = PathBuf::from(i);
             if !path.is_dir() {
@@ -76,258 +265,743 @@ impl PluginManager {
             path_list.push((path, true));
         }
 
-        for entry in path_list {
-            let plugin_dir = entry.0.to_path_buf();
-
-            if plugin_dir.is_dir() {
-                let init_file = plugin_dir.join("init.lua");
-                if init_file.is_file() {
-                    let mut file = std::fs::File::open(init_file).unwrap();
-                    let mut buffer = String::new();
-                    file.read_to_string(&mut buffer).unwrap();
-
-                    let current_plugin_dir = plugin_dir.to_str().unwrap().to_string();
-                    let from_loader = entry.1;
-
-                    lua.globals()
-                        .set("_temp_plugin_dir", current_plugin_dir.clone())?;
-                    lua.globals().set("_temp_from_loader", from_loader)?;
-
-                    let info = lua.load(&buffer).eval::<PluginInfo>();
-                    match info {
-                        Ok(mut info) => {
-                            if name_index.contains_key(info.name.clone()).unwrap_or(false)
-                                && !from_loader
-                            {
-                                // found same name plugin, intercept load
-                                log::warn!(
-                                    "Plugin {} has been intercepted. [mulit-load]",
-                                    info.name
-                                );
-                                continue;
+        let mut plugins: Vec<LuaPlugin> = vec![];
+        let mut name_index: HashMap<String, usize> = HashMap::new();
+
+        for (plugin_dir, from_loader) in path_list {
+            let init_file = plugin_dir.join("init.lua");
+            if !init_file.is_file() {
+                continue;
+            }
+            let dir_name = plugin_dir
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+
+            let load = || -> mlua::Result<Option<LuaPlugin>> {
+                let mut buffer = String::new();
+                std::fs::File::open(&init_file)?.read_to_string(&mut buffer)?;
+
+                let lua = LuaPlugin::create_state(&config, &library_dir, &plugin_dir, from_loader)?;
+                LuaPlugin::reset_budget(&lua, config.instruction_limit);
+                let manager = runtime::block_on(lua.load(&buffer).eval_async::<Table>())?;
+                lua.globals().set("manager", manager.clone())?;
+
+                let info = lua.globals().get::<_, PluginInfo>("manager")?;
+                if name_index.contains_key(&info.name) && !from_loader {
+                    // found same name plugin, intercept load
+                    log::warn!("Plugin {} has been intercepted. [mulit-load]", info.name);
+                    return Ok(None);
+                }
+                let approval = Approval {
+                    name: info.name.clone(),
+                    // local plugins aren't installed, hashing their files makes changes to them
+                    // need a new approval as well
+                    checksum: registry::InstalledPlugin::load(&plugin_dir)
+                        .map(|p| p.checksum)
+                        .or_else(|| registry::dir_checksum(&plugin_dir).ok()),
+                    permissions: info.permissions.clone(),
+                };
+                let key = permissions::approval_key(&plugin_dir, crate_dir.as_deref());
+                if !approvals.approve(&key, &approval) {
+                    return Ok(None);
+                }
+                let sandbox = Sandbox::new(
+                    &info.permissions,
+                    &plugin_dir,
+                    crate_dir.as_deref(),
+                    out_dir.as_deref(),
+                );
+                permissions::apply(&lua, sandbox);
+
+                let inner = PluginInner {
+                    plugin_dir: plugin_dir.to_str().unwrap().to_string(),
+                    from_loader,
+                };
+                manager.set("inner", inner)?;
+
+                // call `on_init` if file "dcp.json" not exists
+                let dcp_file = plugin_dir.join("dcp.json");
+                if !dcp_file.is_file() {
+                    if let Some(func) = info.clone().on_init {
+                        LuaPlugin::reset_budget(&lua, config.instruction_limit);
+                        match runtime::block_on(func.call_async::<_, bool>(()))? {
+                            true => {
+                                // plugin init success, create `dcp.json` file.
+                                let value = json!({
+                                    "name": info.name,
+                                    "author": info.author,
+                                    "repository": info.repository,
+                                    "version": info.version,
+                                    "generate_time": chrono::Local::now().timestamp(),
+                                });
+                                let buffer = serde_json::to_string_pretty(&value).unwrap();
+                                std::fs::File::create(dcp_file)?.write_all(buffer.as_bytes())?;
                             }
-                            info.inner.plugin_dir = current_plugin_dir;
-                            info.inner.from_loader = from_loader;
-
-                            // call `on_init` if file "dcp.json" not exists
-                            let dcp_file = plugin_dir.join("dcp.json");
-                            if !dcp_file.is_file() {
-                                if let Some(func) = info.clone().on_init {
-                                    let result = func.call::<_, bool>(());
-                                    match result {
-                                        Ok(true) => {
-                                            // plugin init success, create `dcp.json` file.
-                                            let mut file = std::fs::File::create(dcp_file).unwrap();
-                                            let value = json!({
-                                                "name": info.name,
-                                                "author": info.author,
-                                                "repository": info.repository,
-                                                "version": info.version,
-                                                "generate_time": chrono::Local::now().timestamp(),
-                                            });
-                                            let buffer =
-                                                serde_json::to_string_pretty(&value).unwrap();
-                                            let buffer = buffer.as_bytes();
-                                            file.write_all(buffer).unwrap();
-
-                                            // insert plugin-info into plugin-manager
-                                            if let Ok(index) =
-                                                name_index.get::<_, u32>(info.name.clone())
-                                            {
-                                                let _ = manager.set(index, info.clone());
-                                            } else {
-                                                let _ = manager.set(index, info.clone());
-                                                index += 1;
-                                                let _ = name_index.set(info.name, index);
-                                            }
-                                        }
-                                        Ok(false) => {
-                                            log::warn!(
-                                                "Plugin init function result is `false`, init failed."
-                                            );
-                                        }
-                                        Err(e) => {
-                                            log::warn!("Plugin init failed: {e}");
-                                        }
-                                    }
-                                }
-                            } else {
-                                if let Ok(index) = name_index.get::<_, u32>(info.name.clone()) {
-                                    let _ = manager.set(index, info.clone());
-                                } else {
-                                    let _ = manager.set(index, info.clone());
-                                    index += 1;
-                                    let _ = name_index.set(info.name, index);
-                                }
+                            false => {
+                                log::warn!("Plugin init function result is `false`, init failed.");
+                                return Ok(None);
                             }
                         }
-                        Err(_e) => {
-                            let dir_name = plugin_dir.file_name().unwrap().to_str().unwrap();
-                            log::error!("Plugin '{dir_name}' load failed.");
-                        }
                     }
                 }
+
+                let name = info.name.clone();
+                let commands = PluginCommand::from_manager(&manager);
+                drop((info, manager));
+                Ok(Some(LuaPlugin {
+                    lua,
+                    name,
+                    from_loader,
+                    instruction_limit: config.instruction_limit,
+                    commands,
+                    plugin_dir: plugin_dir.clone(),
+                    config: config.clone(),
+                }))
+            };
+
+            match catch_unwind(AssertUnwindSafe(load)) {
+                Ok(Ok(Some(plugin))) => match name_index.get(&plugin.name) {
+                    // loaders replace the installed plugin of the same name
+                    Some(&index) => plugins[index] = plugin,
+                    None => {
+                        name_index.insert(plugin.name.clone(), plugins.len());
+                        plugins.push(plugin);
+                    }
+                },
+                Ok(Ok(None)) => {}
+                Ok(Err(e)) => log::error!("Plugin '{dir_name}' load failed: {e}"),
+                Err(_) => log::error!("Plugin '{dir_name}' load failed: it panicked."),
             }
         }
 
-        lua.globals().set("manager", manager).unwrap();
+        *PLUGINS.lock().unwrap() = plugins
+            .into_iter()
+            .map(|plugin| Arc::new(Mutex::new(plugin)))
+            .collect();
 
-        return Ok(());
+        Ok(())
     }
 
-    pub fn on_build_start(crate_config: &CrateConfig, platform: &str) -> anyhow::Result<()> {
-        let lua = LUA.lock().unwrap();
+    /// Call a hook of every plugin
+    ///
+    /// A plugin whose hook fails, panics or exceeds its instruction limit is logged and skipped,
+    /// the other plugins still run.
+    fn call_hooks<A>(select: HookSelector, args: A)
+    where
+        A: for<'lua> Fn(&'lua Lua) -> mlua::Result<Table<'lua>>,
+    {
+        let plugins = PLUGINS.lock().unwrap().clone();
+        Self::call_hooks_of(&plugins, select, args);
+    }
 
-        if !lua.globals().contains_key("manager")? {
-            return Ok(());
+    fn call_hooks_of<A>(plugins: &[Arc<Mutex<LuaPlugin>>], select: HookSelector, args: A)
+    where
+        A: for<'lua> Fn(&'lua Lua) -> mlua::Result<Table<'lua>>,
+    {
+        for plugin in plugins {
+            // a plugin that panicked while holding its lock is broken, but only that one
+            let Ok(plugin) = plugin.lock() else {
+                continue;
+            };
+            match catch_unwind(AssertUnwindSafe(|| {
+                plugin.call_hook::<_, ()>(select, &args)
+            })) {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => log::error!("Plugin `{}` failed: {e}", plugin.name),
+                Err(_) => log::error!("Plugin `{}` panicked.", plugin.name),
+            }
         }
-        let manager = lua.globals().get::<_, Table>("manager")?;
-
-        let args = lua.create_table()?;
-        args.set("name", crate_config.dioxus_config.application.name.clone())?;
-        args.set("platform", platform)?;
-        args.set("out_dir", crate_config.out_dir.to_str().unwrap())?;
-        args.set("asset_dir", crate_config.asset_dir.to_str().unwrap())?;
-
-        for i in 1..(manager.len()? as i32 + 1) {
-            let info = manager.get::<i32, PluginInfo>(i)?;
-            if let Some(func) = info.build.on_start {
-                func.call::<Table, ()>(args.clone())?;
+    }
+
+    /// Call a build hook of every plugin
+    ///
+    /// Plugins fail the build by returning `false` and an optional message, errors in the hooks
+    /// themselves are logged like in [`Self::call_hooks`]. Transform hooks get `content` in their
+    /// arguments and may return a string replacing it, which the next plugin gets.
+    fn call_build_hooks<A>(
+        select: HookSelector,
+        mut content: Option<Vec<u8>>,
+        args: A,
+    ) -> anyhow::Result<Option<Vec<u8>>>
+    where
+        A: for<'lua> Fn(&'lua Lua, Option<&[u8]>) -> mlua::Result<Table<'lua>>,
+    {
+        let plugins = PLUGINS.lock().unwrap().clone();
+        for plugin in plugins {
+            let Ok(plugin) = plugin.lock() else {
+                continue;
+            };
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                plugin.call_hook::<_, HookOutput>(select, |lua| args(lua, content.as_deref()))
+            }));
+            match result {
+                Ok(Ok(None | Some(HookOutput::Continue))) => {}
+                Ok(Ok(Some(HookOutput::Fail(message)))) => {
+                    let message = message.unwrap_or_else(|| "no reason given".into());
+                    return Err(anyhow::anyhow!(
+                        "Plugin `{}` failed the build: {message}",
+                        plugin.name
+                    ));
+                }
+                Ok(Ok(Some(HookOutput::Replace(new)))) => {
+                    if content.is_some() {
+                        content = Some(new);
+                    }
+                }
+                Ok(Err(e)) => log::error!("Plugin `{}` failed: {e}", plugin.name),
+                Err(_) => log::error!("Plugin `{}` panicked.", plugin.name),
             }
         }
+        Ok(content)
+    }
+
+    /// Whether any plugin has the hook picked by `select`
+    pub fn has_hook(select: HookSelector) -> bool {
+        let plugins = PLUGINS.lock().unwrap().clone();
+        plugins.iter().any(|plugin| {
+            let Ok(plugin) = plugin.lock() else {
+                return false;
+            };
+            matches!(plugin.info().map(select), Ok(Some(_)))
+        })
+    }
 
+    pub fn on_build_start(crate_config: &CrateConfig, platform: &str) -> anyhow::Result<()> {
+        Self::call_build_hooks(
+            |info| info.build.on_start,
+            None,
+            |lua, _| build_args(lua, crate_config, platform),
+        )?;
         Ok(())
     }
 
     pub fn on_build_finish(crate_config: &CrateConfig, platform: &str) -> anyhow::Result<()> {
-        let lua = LUA.lock().unwrap();
+        Self::call_build_hooks(
+            |info| info.build.on_finish,
+            None,
+            |lua, _| build_args(lua, crate_config, platform),
+        )?;
+        Ok(())
+    }
 
-        if !lua.globals().contains_key("manager")? {
-            return Ok(());
-        }
-        let manager = lua.globals().get::<_, Table>("manager")?;
-
-        let args = lua.create_table()?;
-        args.set("name", crate_config.dioxus_config.application.name.clone())?;
-        args.set("platform", platform)?;
-        args.set("out_dir", crate_config.out_dir.to_str().unwrap())?;
-        args.set("asset_dir", crate_config.asset_dir.to_str().unwrap())?;
-
-        for i in 1..(manager.len()? as i32 + 1) {
-            let info = manager.get::<i32, PluginInfo>(i)?;
-            if let Some(func) = info.build.on_finish {
-                func.call::<Table, ()>(args.clone())?;
-            }
-        }
+    /// After cargo compiled the `wasm_files` of a web build
+    pub fn after_cargo(crate_config: &CrateConfig, wasm_files: &[PathBuf]) -> anyhow::Result<()> {
+        let wasm_files: Vec<String> = wasm_files
+            .iter()
+            .map(|v| v.to_str().unwrap().to_string())
+            .collect();
+        Self::call_build_hooks(
+            |info| info.build.after_cargo,
+            None,
+            |lua, _| {
+                let args = build_args(lua, crate_config, "web")?;
+                args.set("wasm_files", wasm_files.clone())?;
+                Ok(args)
+            },
+        )?;
+        Ok(())
+    }
 
+    /// After wasm-bindgen generated the glue of the entry `out_name`
+    pub fn after_bindgen(
+        crate_config: &CrateConfig,
+        out_name: &str,
+        js_file: &Path,
+        wasm_file: &Path,
+    ) -> anyhow::Result<()> {
+        Self::call_build_hooks(
+            |info| info.build.after_bindgen,
+            None,
+            |lua, _| {
+                let args = build_args(lua, crate_config, "web")?;
+                args.set("out_name", out_name)?;
+                args.set("js_file", js_file.to_str().unwrap())?;
+                args.set("wasm_file", wasm_file.to_str().unwrap())?;
+                Ok(args)
+            },
+        )?;
         Ok(())
     }
 
-    pub fn on_serve_start(crate_config: &CrateConfig) -> anyhow::Result<()> {
-        let lua = LUA.lock().unwrap();
+    /// After wasm-opt ran, or would have run if it's disabled, over the module of `out_name`
+    pub fn after_wasm_opt(
+        crate_config: &CrateConfig,
+        out_name: &str,
+        wasm_file: &Path,
+    ) -> anyhow::Result<()> {
+        Self::call_build_hooks(
+            |info| info.build.after_wasm_opt,
+            None,
+            |lua, _| {
+                let args = build_args(lua, crate_config, "web")?;
+                args.set("out_name", out_name)?;
+                args.set("wasm_file", wasm_file.to_str().unwrap())?;
+                Ok(args)
+            },
+        )?;
+        Ok(())
+    }
 
-        if !lua.globals().contains_key("manager")? {
-            return Ok(());
-        }
-        let manager = lua.globals().get::<_, Table>("manager")?;
+    /// Let plugins transform an asset copied to `file`, `path` is its url relative to the
+    /// output dir
+    pub fn transform_asset(
+        crate_config: &CrateConfig,
+        path: &str,
+        file: &Path,
+        content: Vec<u8>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let content = Self::call_build_hooks(
+            |info| info.build.transform_asset,
+            Some(content),
+            |lua, content| {
+                let args = build_args(lua, crate_config, "web")?;
+                args.set("path", path)?;
+                args.set("file", file.to_str().unwrap())?;
+                args.set("content", lua.create_string(content.unwrap_or_default())?)?;
+                Ok(args)
+            },
+        )?;
+        Ok(content.unwrap_or_default())
+    }
 
-        let args = lua.create_table()?;
-        args.set("name", crate_config.dioxus_config.application.name.clone())?;
+    /// Let plugins transform the generated `index.html`
+    pub fn transform_html(
+        crate_config: &CrateConfig,
+        html: String,
+        serve: bool,
+    ) -> anyhow::Result<String> {
+        let html = Self::call_build_hooks(
+            |info| info.build.transform_html,
+            Some(html.into_bytes()),
+            |lua, html| {
+                let args = build_args(lua, crate_config, "web")?;
+                args.set("html", lua.create_string(html.unwrap_or_default())?)?;
+                args.set("serve", serve)?;
+                Ok(args)
+            },
+        )?;
+        Ok(String::from_utf8(html.unwrap_or_default())?)
+    }
 
-        for i in 1..(manager.len()? as i32 + 1) {
-            let info = manager.get::<i32, PluginInfo>(i)?;
-            if let Some(func) = info.serve.on_start {
-                func.call::<Table, ()>(args.clone())?;
-            }
-        }
+    pub fn on_serve_start(crate_config: &CrateConfig) -> anyhow::Result<()> {
+        Self::call_hooks(
+            |info| info.serve.on_start,
+            |lua| {
+                let args = lua.create_table()?;
+                args.set("name", crate_config.dioxus_config.application.name.clone())?;
+                Ok(args)
+            },
+        );
+        Self::start_serve_tasks(crate_config);
 
         Ok(())
     }
 
-    pub fn on_serve_rebuild(timestamp: i64, files: Vec<PathBuf>) -> anyhow::Result<()> {
-        let lua = LUA.lock().unwrap();
+    /// Start the `serve.tasks` of every plugin in the background
+    ///
+    /// Each task gets a fresh Lua state of its plugin with the same permissions, and its own
+    /// thread, so it can run for the whole session without holding up hooks. Tasks aren't
+    /// bound by the instruction limit.
+    fn start_serve_tasks(crate_config: &CrateConfig) {
+        let plugins = PLUGINS.lock().unwrap().clone();
+        for plugin in plugins {
+            let Ok(plugin) = plugin.lock() else {
+                continue;
+            };
+            let Ok(Some(tasks)) = plugin.info().map(|info| info.serve.tasks) else {
+                continue;
+            };
+            let names: Vec<String> = tasks
+                .pairs::<String, Function>()
+                .filter_map(|pair| pair.ok().map(|pair| pair.0))
+                .collect();
+
+            for task in names {
+                let name = plugin.name.clone();
+                let plugin_dir = plugin.plugin_dir.clone();
+                let config = plugin.config.clone();
+                let from_loader = plugin.from_loader;
+                let sandbox = permissions::current(&plugin.lua);
+                let app_name = crate_config.dioxus_config.application.name.clone();
+                let out_dir = crate_config.out_dir.clone();
+
+                let run = move || -> mlua::Result<()> {
+                    let library_dir = registry::plugins_dir();
+                    let lua =
+                        LuaPlugin::create_state(&config, &library_dir, &plugin_dir, from_loader)?;
+                    let buffer = std::fs::read_to_string(plugin_dir.join("init.lua"))?;
+                    let manager = runtime::block_on(lua.load(&buffer).eval_async::<Table>())?;
+                    lua.globals().set("manager", manager.clone())?;
+                    let inner = PluginInner {
+                        plugin_dir: plugin_dir.to_str().unwrap().to_string(),
+                        from_loader,
+                    };
+                    manager.set("inner", inner)?;
+                    permissions::apply(&lua, sandbox);
+
+                    let func = manager
+                        .get::<_, Table>("serve")?
+                        .get::<_, Table>("tasks")?
+                        .get::<_, Function>(task.as_str())?;
+                    let args = lua.create_table()?;
+                    args.set("name", app_name)?;
+                    args.set("out_dir", out_dir.to_str().unwrap())?;
+                    runtime::block_on(func.call_async::<_, ()>(args))
+                };
 
-        let manager = lua.globals().get::<_, Table>("manager")?;
+                std::thread::spawn(move || match catch_unwind(AssertUnwindSafe(run)) {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => log::error!("Task `{task}` of plugin `{name}` failed: {e}"),
+                    Err(_) => log::error!("Task `{task}` of plugin `{name}` panicked."),
+                });
+            }
+        }
+    }
 
-        let args = lua.create_table()?;
-        args.set("timestamp", timestamp)?;
+    pub fn on_serve_rebuild(timestamp: i64, files: Vec<PathBuf>) -> anyhow::Result<()> {
         let files: Vec<String> = files
             .iter()
             .map(|v| v.to_str().unwrap().to_string())
             .collect();
-        args.set("changed_files", files)?;
 
-        for i in 1..(manager.len()? as i32 + 1) {
-            let info = manager.get::<i32, PluginInfo>(i)?;
-            if let Some(func) = info.serve.on_rebuild {
-                func.call::<Table, ()>(args.clone())?;
-            }
-        }
+        Self::call_hooks(
+            |info| info.serve.on_rebuild,
+            |lua| {
+                let args = lua.create_table()?;
+                args.set("timestamp", timestamp)?;
+                args.set("changed_files", files.clone())?;
+                Ok(args)
+            },
+        );
 
         Ok(())
     }
 
     pub fn on_serve_shutdown(crate_config: &CrateConfig) -> anyhow::Result<()> {
-        let lua = LUA.lock().unwrap();
+        Self::call_hooks(
+            |info| info.serve.on_shutdown,
+            |lua| {
+                let args = lua.create_table()?;
+                args.set("name", crate_config.dioxus_config.application.name.clone())?;
+                Ok(args)
+            },
+        );
 
-        if !lua.globals().contains_key("manager")? {
-            return Ok(());
+        Ok(())
+    }
+
+    /// The paths of the dev server routes registered by plugins
+    ///
+    /// Paths under `/_dioxus` are reserved, and a path registered by several plugins is handled
+    /// by the first one.
+    pub fn serve_routes() -> Vec<String> {
+        let mut routes: Vec<String> = vec![];
+        let plugins = PLUGINS.lock().unwrap().clone();
+        for plugin in plugins {
+            let Ok(plugin) = plugin.lock() else {
+                continue;
+            };
+            let Ok(Some(table)) = plugin.info().map(|info| info.serve.routes) else {
+                continue;
+            };
+            for path in table
+                .pairs::<String, Function>()
+                .filter_map(|pair| pair.ok())
+            {
+                let path = path.0;
+                if !path.starts_with('/') || path.starts_with("/_dioxus") {
+                    log::warn!("Plugin `{}` can't serve `{path}`.", plugin.name);
+                } else if !routes.contains(&path) {
+                    routes.push(path);
+                }
+            }
         }
-        let manager = lua.globals().get::<_, Table>("manager")?;
+        routes
+    }
 
-        let args = lua.create_table()?;
-        args.set("name", crate_config.dioxus_config.application.name.clone())?;
+    /// Handle a request to the plugin route at `path`
+    ///
+    /// Errors in the handler become a `500` response, `None` if no plugin serves `path`. The
+    /// plugin is locked until the handler returns or runs into [`SERVE_TIMEOUT`].
+    pub fn handle_route(path: &str, request: &PluginRequest) -> Option<PluginResponse> {
+        let plugins = PLUGINS.lock().unwrap().clone();
+        for plugin in plugins {
+            let Ok(plugin) = plugin.lock() else {
+                continue;
+            };
+            let handler = || -> mlua::Result<Option<PluginResponse>> {
+                let Some(routes) = plugin.info()?.serve.routes else {
+                    return Ok(None);
+                };
+                let Some(handler) = routes.get::<_, Option<Function>>(path)? else {
+                    return Ok(None);
+                };
+                LuaPlugin::reset_budget(&plugin.lua, plugin.instruction_limit);
+                call_with_timeout(handler, request.to_table(&plugin.lua)?).map(Some)
+            };
+            let message = match catch_unwind(AssertUnwindSafe(handler)) {
+                Ok(Ok(None)) => continue,
+                Ok(Ok(Some(response))) => return Some(response),
+                Ok(Err(e)) => format!("Plugin `{}` failed: {e}", plugin.name),
+                Err(_) => format!("Plugin `{}` panicked.", plugin.name),
+            };
+            log::error!("{message}");
+            return Some(PluginResponse::error(message));
+        }
+        None
+    }
 
-        for i in 1..(manager.len()? as i32 + 1) {
-            let info = manager.get::<i32, PluginInfo>(i)?;
-            if let Some(func) = info.serve.on_shutdown {
-                func.call::<Table, ()>(args.clone())?;
+    /// Pass a response of the dev server through the middleware of every plugin
+    ///
+    /// A middleware returning `nil` keeps the response, errors and timeouts are logged and skipped.
+    pub fn transform_response(
+        request: &PluginRequest,
+        mut response: PluginResponse,
+    ) -> PluginResponse {
+        let plugins = PLUGINS.lock().unwrap().clone();
+        for plugin in plugins {
+            let Ok(plugin) = plugin.lock() else {
+                continue;
+            };
+            let middleware = || -> mlua::Result<Option<PluginResponse>> {
+                let Some(middleware) = plugin.info()?.serve.middleware else {
+                    return Ok(None);
+                };
+                let lua = &plugin.lua;
+                LuaPlugin::reset_budget(lua, plugin.instruction_limit);
+                let args = (request.to_table(lua)?, response.to_table(lua)?);
+                call_with_timeout(middleware, args)
+            };
+            match catch_unwind(AssertUnwindSafe(middleware)) {
+                Ok(Ok(Some(new))) => response = new,
+                Ok(Ok(None)) => {}
+                Ok(Err(e)) => log::error!("Plugin `{}` failed: {e}", plugin.name),
+                Err(_) => log::error!("Plugin `{}` panicked.", plugin.name),
             }
         }
-
-        Ok(())
+        response
     }
 
-    pub fn init_plugin_dir() -> PathBuf {
-        let app_path = app_path();
-        let plugin_path = app_path.join("plugins");
-        if !plugin_path.is_dir() {
-            log::info!("📖 Start to init plugin library ...");
-            let url = "https://github.com/DioxusLabs/cli-plugin-library";
-            if let Err(err) = clone_repo(&plugin_path, url) {
-                log::error!("Failed to init plugin dir, error caused by {}. ", err);
+    /// Add the commands of every plugin to the `cli`, as `<plugin> <command>`
+    pub fn augment_cli(mut cli: clap::Command) -> clap::Command {
+        let plugins = PLUGINS.lock().unwrap().clone();
+        for plugin in plugins {
+            let Ok(plugin) = plugin.lock() else {
+                continue;
+            };
+            if plugin.commands.is_empty() {
+                continue;
             }
+            let name = commands::cli_name(&plugin.name);
+            if cli.find_subcommand(&name).is_some() {
+                log::warn!(
+                    "The commands of plugin `{}` are hidden by the `{name}` command.",
+                    plugin.name
+                );
+                continue;
+            }
+            let command = clap::Command::new(name)
+                .about(format!("Commands of the `{}` plugin", plugin.name))
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommands(plugin.commands.iter().map(PluginCommand::to_clap));
+            cli = cli.subcommand(command);
         }
-        plugin_path
+        cli
+    }
+
+    /// Run the command of the plugin available as `dioxus <name>`, `None` if there is no such
+    /// plugin
+    pub fn run_command(name: &str, matches: &clap::ArgMatches) -> Option<anyhow::Result<()>> {
+        let plugins = PLUGINS.lock().unwrap().clone();
+        let plugin = plugins.into_iter().find(|plugin| {
+            plugin
+                .lock()
+                .map(|plugin| commands::cli_name(&plugin.name) == name)
+                .unwrap_or(false)
+        })?;
+        let plugin = plugin.lock().unwrap();
+
+        let (command_name, matches) = matches.subcommand()?;
+        let command = plugin
+            .commands
+            .iter()
+            .find(|command| command.name == command_name)?;
+
+        // plugin commands work outside of a crate too, the context is just emptier
+        let crate_config = CrateConfig::new().ok();
+        let run = || -> mlua::Result<HookOutput> {
+            let lua = &plugin.lua;
+            let ctx = lua.create_table()?;
+            if let Some(config) = &crate_config {
+                ctx.set("name", config.dioxus_config.application.name.clone())?;
+                ctx.set(
+                    "platform",
+                    config.dioxus_config.application.default_platform.clone(),
+                )?;
+                ctx.set("crate_dir", config.crate_dir.to_str().unwrap())?;
+                ctx.set("workspace_dir", config.workspace_dir.to_str().unwrap())?;
+                ctx.set("target_dir", config.target_dir.to_str().unwrap())?;
+                ctx.set("out_dir", config.out_dir.to_str().unwrap())?;
+                ctx.set("asset_dir", config.asset_dir.to_str().unwrap())?;
+                ctx.set("release", config.release)?;
+            }
+            let args = command.args_table(lua, matches)?;
+
+            let run = lua
+                .globals()
+                .get::<_, Table>("manager")?
+                .get::<_, Table>("commands")?
+                .get::<_, Table>(command.name.as_str())?
+                .get::<_, Function>("run")?;
+            LuaPlugin::reset_budget(lua, plugin.instruction_limit);
+            runtime::block_on(run.call_async((ctx, args)))
+        };
+
+        Some(match catch_unwind(AssertUnwindSafe(run)) {
+            Ok(Ok(HookOutput::Fail(message))) => Err(anyhow::anyhow!(
+                "{}",
+                message.unwrap_or_else(|| format!("`{name} {command_name}` failed."))
+            )),
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(e)) => Err(e.into()),
+            Err(_) => Err(anyhow::anyhow!("Plugin `{}` panicked.", plugin.name)),
+        })
     }
 
     pub fn plugin_list() -> Vec<String> {
-        let mut res = vec![];
-
-        if let Ok(lua) = LUA.lock() {
-            let list = lua
-                .load(mlua::chunk!(
-                    local list = {}
-                    for key, value in ipairs(manager) do
-                        table.insert(list, {name = value.name, loader = value.inner.from_loader})
-                    end
-                    return list
-                ))
-                .eval::<Vec<Table>>()
-                .unwrap_or_default();
-            for i in list {
-                let name = i.get::<_, String>("name").unwrap();
-                let loader = i.get::<_, bool>("loader").unwrap();
-
-                let text = if loader {
-                    format!("{name} [:loader]")
+        let plugins = PLUGINS.lock().unwrap().clone();
+        plugins
+            .iter()
+            .filter_map(|plugin| {
+                let plugin = plugin.lock().ok()?;
+                Some(if plugin.from_loader {
+                    format!("{} [:loader]", plugin.name)
                 } else {
-                    name
-                };
-                res.push(text);
-            }
+                    plugin.name.clone()
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn load_plugin(name: &str, code: &str) -> LuaPlugin {
+        let config = PluginConfig::from_toml_value(toml::Value::Table(Default::default()));
+        let dir = std::env::temp_dir();
+        let lua = LuaPlugin::create_state(&config, &dir, &dir, false).unwrap();
+        let explode = lua
+            .create_function(|_, ()| -> mlua::Result<()> { panic!("the plugin exploded") })
+            .unwrap();
+        lua.globals().set("explode", explode).unwrap();
+        let manager: Table = lua.load(code).eval().unwrap();
+        lua.globals().set("manager", manager).unwrap();
+        LuaPlugin {
+            lua,
+            name: name.into(),
+            from_loader: false,
+            instruction_limit: config.instruction_limit,
+            commands: vec![],
+            plugin_dir: dir,
+            config,
         }
+    }
+
+    fn on_start(info: PluginInfo) -> Option<Function> {
+        info.build.on_start
+    }
+
+    fn global<'lua, T: mlua::FromLua<'lua>>(plugin: &'lua LuaPlugin, name: &str) -> T {
+        plugin.lua.globals().get(name).unwrap()
+    }
+
+    #[test]
+    fn plugins_have_their_own_globals() {
+        let first = load_plugin(
+            "first",
+            r#"
+            name = "first"
+            string.upper = function () return "clobbered" end
+            return { build = { on_start = function () seen = name end } }
+            "#,
+        );
+        let second = load_plugin(
+            "second",
+            r#"
+            return { build = { on_start = function () seen = name; upper = string.upper("x") end } }
+            "#,
+        );
+        let plugins = [first, second].map(|plugin| Arc::new(Mutex::new(plugin)));
+        PluginManager::call_hooks_of(&plugins, on_start, |lua| lua.create_table());
+
+        let first = plugins[0].lock().unwrap();
+        let second = plugins[1].lock().unwrap();
+        assert_eq!(global::<String>(&first, "seen"), "first");
+        assert_eq!(global::<Option<String>>(&second, "seen"), None);
+        assert_eq!(global::<String>(&second, "upper"), "X");
+    }
+
+    #[test]
+    fn failing_plugins_dont_stop_the_others() {
+        let plugins = [
+            load_plugin(
+                "erroring",
+                r#"return { build = { on_start = function () error("broken") end } }"#,
+            ),
+            load_plugin(
+                "panicking",
+                r#"return { build = { on_start = function () explode() end } }"#,
+            ),
+            load_plugin(
+                "working",
+                r#"return { build = { on_start = function () ran = true end } }"#,
+            ),
+        ]
+        .map(|plugin| Arc::new(Mutex::new(plugin)));
+        PluginManager::call_hooks_of(&plugins, on_start, |lua| lua.create_table());
+
+        assert!(global::<bool>(&plugins[2].lock().unwrap(), "ran"));
+        // the panic was caught while the plugin was locked, it can still be used
+        assert!(plugins[1].lock().is_ok());
+    }
+
+    #[test]
+    fn endless_loops_hit_the_instruction_limit() {
+        let plugin = load_plugin(
+            "looping",
+            r#"return { build = { on_start = function () while true do end end } }"#,
+        );
+        assert_eq!(plugin.instruction_limit, types::DEFAULT_INSTRUCTION_LIMIT);
+
+        let err = plugin
+            .call_hook::<_, ()>(on_start, |lua| lua.create_table())
+            .unwrap_err();
+        assert!(err.to_string().contains("instruction limit exceeded"));
+    }
 
-        res
+    #[test]
+    fn hook_outputs() {
+        let lua = Lua::new();
+        let output = |code: &str| lua.load(code).eval::<HookOutput>();
+
+        assert!(matches!(output("return"), Ok(HookOutput::Continue)));
+        assert!(matches!(output("return nil"), Ok(HookOutput::Continue)));
+        assert!(matches!(output("return true"), Ok(HookOutput::Continue)));
+        assert!(matches!(output("return false"), Ok(HookOutput::Fail(None))));
+        assert!(matches!(
+            output(r#"return false, "missing icon""#),
+            Ok(HookOutput::Fail(Some(message))) if message == "missing icon"
+        ));
+        assert!(matches!(
+            output(r#"return "<html></html>""#),
+            Ok(HookOutput::Replace(content)) if content == b"<html></html>"
+        ));
+        assert!(output("return 42").is_err());
+        assert!(output("return {}").is_err());
     }
 }
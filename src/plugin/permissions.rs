@@ -0,0 +1,571 @@
+//! What plugins are allowed to do
+//!
+//! Plugins declare the permissions they need in their manifest:
+//!
+//! ```lua
+//! manager.permissions = {
+//!     -- relative to the crate directory, or to the output directory with `$out_dir`
+//!     fs = { "src", "$out_dir" },
+//!     commands = { "npx" },
+//!     -- `*.example.com` matches every subdomain
+//!     network = { "github.com", "*.githubusercontent.com" },
+//!     -- `NODE_*` matches every variable starting with `NODE_`
+//!     env = { "CI", "NODE_*" },
+//! }
+//! ```
+//!
+//! The user approves them once, and the `plugin_lib` interfaces check every call against the
+//! permissions of the plugin, which are stored in its Lua state. A plugin can always access its
+//! own directory.
+use super::lock::PluginLock;
+use crate::tools::app_path;
+use mlua::{FromLua, Function, Lua, MultiValue, Table, ToLua, ToLuaMulti};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+/// The approvals of plugins that aren't declared by the project
+const APPROVALS_FILE: &str = "plugin-permissions.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginPermissions {
+    #[serde(default)]
+    pub fs: Vec<String>,
+    #[serde(default)]
+    pub commands: Vec<String>,
+    #[serde(default)]
+    pub network: Vec<String>,
+    #[serde(default)]
+    pub env: Vec<String>,
+}
+
+impl<'lua> FromLua<'lua> for PluginPermissions {
+    fn from_lua(lua_value: mlua::Value<'lua>, _lua: &'lua Lua) -> mlua::Result<Self> {
+        let mut res = Self::default();
+        if let mlua::Value::Table(tab) = lua_value {
+            if let Ok(v) = tab.get::<_, Vec<String>>("fs") {
+                res.fs = v;
+            }
+            if let Ok(v) = tab.get::<_, Vec<String>>("commands") {
+                res.commands = v;
+            }
+            if let Ok(v) = tab.get::<_, Vec<String>>("network") {
+                res.network = v;
+            }
+            if let Ok(v) = tab.get::<_, Vec<String>>("env") {
+                res.env = v;
+            }
+        }
+        Ok(res)
+    }
+}
+
+impl<'lua> ToLua<'lua> for PluginPermissions {
+    fn to_lua(self, lua: &'lua Lua) -> mlua::Result<mlua::Value<'lua>> {
+        let res = lua.create_table()?;
+        res.set("fs", self.fs)?;
+        res.set("commands", self.commands)?;
+        res.set("network", self.network)?;
+        res.set("env", self.env)?;
+        Ok(mlua::Value::Table(res))
+    }
+}
+
+impl PluginPermissions {
+    pub fn is_empty(&self) -> bool {
+        self.fs.is_empty()
+            && self.commands.is_empty()
+            && self.network.is_empty()
+            && self.env.is_empty()
+    }
+
+    /// Whether everything in `self` was already approved in `approved`
+    pub fn is_covered_by(&self, approved: &PluginPermissions) -> bool {
+        self.fs.iter().all(|v| approved.fs.contains(v))
+            && self.commands.iter().all(|v| approved.commands.contains(v))
+            && self.network.iter().all(|v| approved.network.contains(v))
+            && self.env.iter().all(|v| approved.env.contains(v))
+    }
+}
+
+/// What the user approved for the plugin at one location
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Approval {
+    /// The name the plugin gives itself, only shown to the user
+    #[serde(default)]
+    pub name: String,
+    /// The checksum of the plugin files, for installed and local plugins alike
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    #[serde(default)]
+    pub permissions: PluginPermissions,
+}
+
+impl Approval {
+    /// Whether `self` was already approved in `approved`, by the same plugin
+    fn is_covered_by(&self, approved: &Approval) -> bool {
+        self.checksum == approved.checksum && self.permissions.is_covered_by(&approved.permissions)
+    }
+}
+
+/// The key of the approval of the plugin in `plugin_dir`
+///
+/// Plugins can't pick it themselves, unlike their name. Installed and cached plugins are keyed
+/// relative to the app path and project plugins relative to the crate, so lockfiles are the same
+/// on every machine.
+pub fn approval_key(plugin_dir: &Path, crate_dir: Option<&Path>) -> String {
+    let relative = plugin_dir
+        .strip_prefix(app_path())
+        .ok()
+        .or_else(|| crate_dir.and_then(|dir| plugin_dir.strip_prefix(dir).ok()))
+        .unwrap_or(plugin_dir);
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+/// Where the approvals of the loaded plugins are stored
+///
+/// Projects with `[plugin.dependencies]` keep them in their lockfile, so they are shared with
+/// everyone working on the project. Otherwise they are stored globally.
+pub enum ApprovalStore {
+    Project(PathBuf),
+    Global,
+}
+
+impl ApprovalStore {
+    fn load(&self) -> BTreeMap<String, Approval> {
+        match self {
+            Self::Project(crate_dir) => PluginLock::load(crate_dir)
+                .map(|lock| lock.permissions)
+                .unwrap_or_default(),
+            Self::Global => std::fs::read_to_string(app_path().join(APPROVALS_FILE))
+                .ok()
+                .and_then(|text| toml::from_str(&text).ok())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn save(&self, key: &str, approval: &Approval) -> anyhow::Result<()> {
+        match self {
+            Self::Project(crate_dir) => {
+                let mut lock = PluginLock::load(crate_dir)?;
+                lock.permissions.insert(key.to_string(), approval.clone());
+                lock.save(crate_dir)
+            }
+            Self::Global => {
+                let mut approvals = self.load();
+                approvals.insert(key.to_string(), approval.clone());
+                std::fs::write(
+                    app_path().join(APPROVALS_FILE),
+                    toml::to_string_pretty(&approvals)?,
+                )?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Whether the plugin at `key` may use the permissions of `approval`, asking the user if they
+    /// weren't approved yet
+    ///
+    /// Updated plugins have a new checksum, so they are approved again.
+    pub fn approve(&self, key: &str, approval: &Approval) -> bool {
+        let name = &approval.name;
+        let permissions = &approval.permissions;
+        if permissions.is_empty() {
+            return true;
+        }
+        if let Some(approved) = self.load().get(key) {
+            if approval.is_covered_by(approved) {
+                return true;
+            }
+        }
+
+        if !atty::is(atty::Stream::Stdin) {
+            log::warn!(
+                "Plugin `{name}` wasn't loaded because its permissions aren't approved. \
+                 Run the CLI in a terminal to approve them."
+            );
+            return false;
+        }
+
+        println!("Plugin `{name}` in `{key}` requests these permissions:");
+        for path in &permissions.fs {
+            println!("  - access files in `{path}`");
+        }
+        for command in &permissions.commands {
+            println!("  - run `{command}`");
+        }
+        for host in &permissions.network {
+            println!("  - download from `{host}`");
+        }
+        for var in &permissions.env {
+            println!("  - read the environment variable `{var}`");
+        }
+        print!("Allow? [y/N] ");
+        let _ = std::io::stdout().flush();
+
+        let mut answer = String::new();
+        let _ = std::io::stdin().read_line(&mut answer);
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            log::warn!("Plugin `{name}` wasn't loaded because its permissions were denied.");
+            return false;
+        }
+
+        if let Err(err) = self.save(key, approval) {
+            log::warn!("Failed to store the approval of plugin `{name}`: {err}");
+        }
+        true
+    }
+}
+
+/// The permissions of one plugin, resolved against the project
+#[derive(Debug, Default)]
+pub struct Sandbox {
+    /// Relative paths are resolved against it, or against the current directory without a crate
+    crate_dir: Option<PathBuf>,
+    fs: Vec<PathBuf>,
+    commands: Vec<String>,
+    network: Vec<String>,
+    env: Vec<String>,
+}
+
+impl Sandbox {
+    /// `crate_dir` and `out_dir` are `None` outside of a project, which drops the permissions
+    /// relative to them
+    pub fn new(
+        permissions: &PluginPermissions,
+        plugin_dir: &Path,
+        crate_dir: Option<&Path>,
+        out_dir: Option<&Path>,
+    ) -> Self {
+        let mut fs = vec![resolve(plugin_dir)];
+        for path in &permissions.fs {
+            let (base, relative) = match path.strip_prefix("$out_dir") {
+                Some(relative) => (out_dir, relative.trim_start_matches(['/', '\\'])),
+                None => (crate_dir, path.as_str()),
+            };
+            let relative = Path::new(relative);
+            // a permission may not reach outside of its base directory
+            if relative.is_absolute()
+                || relative
+                    .components()
+                    .any(|c| matches!(c, Component::ParentDir | Component::Prefix(_)))
+            {
+                log::warn!("Ignoring plugin permission for `{path}`, it leaves the project.");
+                continue;
+            }
+            if let Some(base) = base {
+                fs.push(resolve(&base.join(relative)));
+            }
+        }
+
+        Self {
+            crate_dir: crate_dir.map(Path::to_path_buf),
+            fs,
+            commands: permissions.commands.clone(),
+            network: permissions.network.clone(),
+            env: permissions.env.clone(),
+        }
+    }
+
+    /// The absolute `path` if the plugin may access it
+    ///
+    /// Symlinks are resolved first, so a link inside an allowed directory can't reach outside of
+    /// it.
+    pub fn check_path(&self, path: &str) -> mlua::Result<PathBuf> {
+        let base = match &self.crate_dir {
+            Some(crate_dir) => crate_dir.clone(),
+            None => std::env::current_dir()?,
+        };
+        let absolute = resolve(&base.join(path));
+        if self.fs.iter().any(|root| absolute.starts_with(root)) {
+            Ok(absolute)
+        } else {
+            Err(denied(format!("access to `{path}`")))
+        }
+    }
+
+    /// Only bare program names are allowed, which are looked up in `PATH`, so a plugin can't run
+    /// an executable of its own that happens to have an allowed name
+    pub fn check_command(&self, program: &str) -> mlua::Result<()> {
+        let bare = !program.contains(['/', '\\']);
+        if bare && self.commands.iter().any(|allowed| allowed == program) {
+            Ok(())
+        } else {
+            Err(denied(format!("running `{program}`")))
+        }
+    }
+
+    pub fn check_url(&self, url: &str) -> mlua::Result<()> {
+        let host = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(String::from))
+            .unwrap_or_default();
+        let allowed = self
+            .network
+            .iter()
+            .any(|pattern| match pattern.strip_prefix("*.") {
+                Some(domain) => host == domain || host.ends_with(&format!(".{domain}")),
+                None => *pattern == host,
+            });
+        if allowed && !host.is_empty() {
+            Ok(())
+        } else {
+            Err(denied(format!("downloading from `{url}`")))
+        }
+    }
+
+    pub fn check_env(&self, name: &str) -> mlua::Result<()> {
+        if self.allows_env(name) {
+            Ok(())
+        } else {
+            Err(denied(format!("reading `{name}`")))
+        }
+    }
+
+    /// Whether the plugin may read the environment variable `name`
+    pub fn allows_env(&self, name: &str) -> bool {
+        self.env
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => pattern == name,
+            })
+    }
+}
+
+fn denied(what: String) -> mlua::Error {
+    mlua::Error::RuntimeError(format!(
+        "permission denied: {what}, add it to `manager.permissions`"
+    ))
+}
+
+/// Resolve `.` and `..` without touching the file system, the path may not exist yet
+fn normalize(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                res.pop();
+            }
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Resolve the symlinks of the deepest part of `path` that exists, the rest may not exist yet
+fn resolve(path: &Path) -> PathBuf {
+    let path = normalize(path);
+    let mut existing = path.as_path();
+    let mut missing = vec![];
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return missing
+                .iter()
+                .rev()
+                .fold(canonical, |res, name| res.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => return path.clone(),
+        }
+    }
+}
+
+/// Run every call into the plugin owning `lua` with the permissions of `sandbox`
+pub fn apply(lua: &Lua, sandbox: impl Into<Arc<Sandbox>>) {
+    lua.set_app_data::<Arc<Sandbox>>(sandbox.into());
+}
+
+/// The sandbox of the running plugin
+pub fn current(lua: &Lua) -> Arc<Sandbox> {
+    lua.app_data_ref::<Arc<Sandbox>>()
+        .map(|sandbox| Arc::clone(&sandbox))
+        .unwrap_or_default()
+}
+
+/// Remove the parts of the standard library that get around the sandbox
+///
+/// Only the preload searcher is kept, native modules could do anything. Lua files are found on
+/// `package.path` as usual, but only loaded if the plugin may read them.
+pub fn restrict_globals(lua: &Lua) -> mlua::Result<()> {
+    lua.load(mlua::chunk!(
+        io = nil
+        dofile = nil
+        loadfile = nil
+        os.execute = nil
+        os.exit = nil
+        os.getenv = nil
+        os.remove = nil
+        os.rename = nil
+        os.tmpname = nil
+        package.loadlib = nil
+        package.cpath = ""
+        package.searchers = { package.searchers[1] }
+    ))
+    .exec()?;
+    let searchers: Table = lua.globals().get::<_, Table>("package")?.get("searchers")?;
+    searchers.raw_set(2, lua.create_function(search_lua_module)?)?;
+    Ok(())
+}
+
+/// The Lua file searcher of `require`, checking the file against the sandbox before loading it
+fn search_lua_module(lua: &Lua, name: String) -> mlua::Result<MultiValue> {
+    let package: Table = lua.globals().get("package")?;
+    let searchpath: Function = package.get("searchpath")?;
+    let (file, not_found): (Option<String>, Option<String>) =
+        searchpath.call((name, package.get::<_, String>("path")?))?;
+    let Some(file) = file else {
+        return not_found.to_lua_multi(lua);
+    };
+    let file = match current(lua).check_path(&file) {
+        Ok(file) => file,
+        Err(err) => return err.to_string().to_lua_multi(lua),
+    };
+    let source = std::fs::read(&file)?;
+    let loader = lua
+        .load(&source)
+        .set_name(&format!("@{}", file.display()))?
+        .into_function()?;
+    (loader, file.to_string_lossy().to_string()).to_lua_multi(lua)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sandbox_paths_and_hosts() {
+        let permissions = PluginPermissions {
+            fs: vec!["src".into(), "$out_dir/assets".into(), "../secrets".into()],
+            commands: vec!["npx".into()],
+            network: vec!["*.github.com".into()],
+            env: vec!["CI".into(), "NODE_*".into()],
+        };
+        let sandbox = Sandbox::new(
+            &permissions,
+            Path::new("/plugins/demo"),
+            Some(Path::new("/app")),
+            Some(Path::new("/app/dist")),
+        );
+
+        assert!(sandbox.check_path("/app/src/main.rs").is_ok());
+        assert!(sandbox.check_path("/app/dist/assets/a.css").is_ok());
+        assert!(sandbox.check_path("/plugins/demo/bin").is_ok());
+        assert!(sandbox.check_path("/app/src/../Cargo.toml").is_err());
+        assert!(sandbox.check_path("/app/dist/index.html").is_err());
+        assert!(sandbox.check_path("/secrets").is_err());
+
+        assert!(sandbox.check_command("npx").is_ok());
+        assert!(sandbox.check_command("/tmp/x/npx").is_err());
+        assert!(sandbox.check_command("./npx").is_err());
+        assert!(sandbox.check_command("rm").is_err());
+
+        assert!(sandbox.check_url("https://api.github.com/repos").is_ok());
+        assert!(sandbox.check_url("https://github.com.evil.io").is_err());
+
+        assert!(sandbox.check_env("CI").is_ok());
+        assert!(sandbox.check_env("NODE_ENV").is_ok());
+        assert!(sandbox.check_env("CI_TOKEN").is_err());
+    }
+    #[test]
+    fn approvals_are_tied_to_the_plugin_files() {
+        let approved = Approval {
+            name: "demo".into(),
+            checksum: Some("sha256:aaaa".into()),
+            permissions: PluginPermissions {
+                commands: vec!["npx".into()],
+                ..Default::default()
+            },
+        };
+        assert!(approved.is_covered_by(&approved));
+
+        let updated = Approval {
+            checksum: Some("sha256:bbbb".into()),
+            ..approved.clone()
+        };
+        assert!(!updated.is_covered_by(&approved));
+
+        let key = approval_key(Path::new("/app/plugins/demo"), Some(Path::new("/app")));
+        assert_eq!(key, "plugins/demo");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sandbox_resolves_symlinks() {
+        let dir = std::env::temp_dir().join(format!("dioxus-sandbox-{}", std::process::id()));
+        let root = dir.join("app");
+        let outside = dir.join("outside");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("src/link")).unwrap();
+
+        let permissions = PluginPermissions {
+            fs: vec!["src".into()],
+            ..Default::default()
+        };
+        let sandbox = Sandbox::new(&permissions, &dir.join("plugin"), Some(&root), None);
+        let inside = root.join("src/new/file.txt");
+        let through_link = root.join("src/link/secret.txt");
+        let inside_ok = sandbox.check_path(inside.to_str().unwrap()).is_ok();
+        let link_ok = sandbox.check_path(through_link.to_str().unwrap()).is_ok();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(inside_ok);
+        assert!(!link_ok);
+    }
+
+    #[test]
+    fn relative_paths_start_at_the_crate() {
+        let permissions = PluginPermissions {
+            fs: vec!["src".into()],
+            ..Default::default()
+        };
+        let sandbox = Sandbox::new(
+            &permissions,
+            Path::new("/plugins/demo"),
+            Some(Path::new("/app")),
+            None,
+        );
+        assert_eq!(
+            sandbox.check_path("src/main.rs").unwrap(),
+            Path::new("/app/src/main.rs")
+        );
+        assert!(sandbox.check_path("Cargo.toml").is_err());
+    }
+
+    #[test]
+    fn require_is_sandboxed() {
+        let dir = std::env::temp_dir().join(format!("dioxus-require-{}", std::process::id()));
+        let plugin_dir = dir.join("plugin");
+        let outside = dir.join("outside");
+        std::fs::create_dir_all(&plugin_dir).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(plugin_dir.join("helper.lua"), "return 'helper'").unwrap();
+        std::fs::write(outside.join("secret.lua"), "return 'secret'").unwrap();
+
+        let lua = Lua::new();
+        restrict_globals(&lua).unwrap();
+        let sandbox = Sandbox::new(&PluginPermissions::default(), &plugin_dir, None, None);
+        apply(&lua, sandbox);
+        let package: Table = lua.globals().get("package").unwrap();
+        let search_path = |dir: &Path| dir.join("?.lua").to_string_lossy().to_string();
+
+        package.set("path", search_path(&plugin_dir)).unwrap();
+        let helper = lua.load("return require('helper')").eval::<String>();
+        package.set("path", search_path(&outside)).unwrap();
+        let secret = lua.load("return require('secret')").eval::<String>();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(helper.unwrap(), "helper");
+        assert!(secret.is_err());
+    }
+}
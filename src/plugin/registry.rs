@@ -0,0 +1,586 @@
+//! Installing plugins from the plugin index, git repositories and local directories
+//!
+//! The index is a TOML file listing every published plugin with the repository it lives in and
+//! the released versions:
+//!
+//! ```toml
+//! [[plugin]]
+//! name = "tailwind"
+//! description = "Build tailwind styles"
+//! repository = "https://github.com/DioxusLabs/tailwind-plugin"
+//!
+//! [[plugin.version]]
+//! version = "0.1.0"
+//! rev = "v0.1.0"
+//! checksum = "sha256:..."
+//! ```
+//!
+//! `rev` is the git tag, branch or commit of the release. `checksum` covers the files of the
+//! plugin, see [`dir_checksum`]. Every plugin is installed into `app_path()/plugins/<name>`.
+use crate::tools::{app_path, clone_repo};
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The index used when neither `--index` nor `DIOXUS_PLUGIN_INDEX` is given
+pub const DEFAULT_INDEX: &str =
+    "https://raw.githubusercontent.com/DioxusLabs/cli-plugin-library/main/index.toml";
+/// Points to another index, like a mirror or a local file for offline use
+pub const INDEX_ENV: &str = "DIOXUS_PLUGIN_INDEX";
+/// Written into every installed plugin to remember where it came from
+pub const INSTALL_FILE: &str = "dioxus-plugin.toml";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PluginIndex {
+    #[serde(default)]
+    pub plugin: Vec<IndexEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IndexEntry {
+    pub name: String,
+    pub description: Option<String>,
+    pub repository: String,
+    #[serde(default)]
+    pub version: Vec<IndexVersion>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IndexVersion {
+    pub version: String,
+    pub rev: Option<String>,
+    pub checksum: Option<String>,
+}
+
+impl PluginIndex {
+    /// Load the index from `location`, a url or a file path
+    ///
+    /// Remote indexes are cached, so the last fetched copy is used when the network is down.
+    pub async fn load(location: Option<&str>) -> anyhow::Result<Self> {
+        let env_location = std::env::var(INDEX_ENV).ok();
+        let location = location
+            .or(env_location.as_deref())
+            .unwrap_or(DEFAULT_INDEX);
+
+        let text = if location.starts_with("http://") || location.starts_with("https://") {
+            let cache = app_path().join("plugin-index.toml");
+            match fetch(location).await {
+                Ok(text) => {
+                    let _ = std::fs::write(&cache, &text);
+                    text
+                }
+                Err(err) if cache.is_file() => {
+                    log::warn!("Failed to fetch the plugin index, using the cached copy: {err}");
+                    std::fs::read_to_string(&cache)?
+                }
+                Err(err) => return Err(err),
+            }
+        } else {
+            std::fs::read_to_string(location)
+                .with_context(|| format!("Failed to read the plugin index `{location}`"))?
+        };
+
+        toml::from_str(&text).context("Invalid plugin index")
+    }
+
+    pub fn find(&self, name: &str) -> Option<&IndexEntry> {
+        self.plugin.iter().find(|entry| entry.name == name)
+    }
+
+    /// The plugins whose name or description contain `query`
+    pub fn search(&self, query: &str) -> Vec<&IndexEntry> {
+        let query = query.to_lowercase();
+        self.plugin
+            .iter()
+            .filter(|entry| {
+                entry.name.to_lowercase().contains(&query)
+                    || entry
+                        .description
+                        .as_deref()
+                        .unwrap_or_default()
+                        .to_lowercase()
+                        .contains(&query)
+            })
+            .collect()
+    }
+}
+
+impl IndexEntry {
+    /// The highest released version
+    pub fn latest(&self) -> Option<&IndexVersion> {
+        self.version
+            .iter()
+            .max_by_key(|v| semver::Version::parse(&v.version).ok())
+    }
+
+    /// The highest version matching `req`, like `0.1` or `>=0.2, <0.4`
+    pub fn matching(&self, req: &semver::VersionReq) -> Option<&IndexVersion> {
+        self.version
+            .iter()
+            .filter_map(|v| Some((semver::Version::parse(&v.version).ok()?, v)))
+            .filter(|(version, _)| req.matches(version))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, v)| v)
+    }
+
+    /// The exact `version` if it was released, otherwise the highest one matching it as a
+    /// requirement
+    pub fn get_version(&self, version: Option<&str>) -> Option<&IndexVersion> {
+        let Some(version) = version else {
+            return self.latest();
+        };
+        self.version
+            .iter()
+            .find(|v| v.version == version)
+            .or_else(|| self.matching(&semver::VersionReq::parse(version).ok()?))
+    }
+}
+
+async fn fetch(url: &str) -> anyhow::Result<String> {
+    let response = reqwest::get(url).await?.error_for_status()?;
+    Ok(response.text().await?)
+}
+
+/// Where a plugin is installed from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PluginSource {
+    /// A plugin of the index, at a version or the latest one
+    Index {
+        name: String,
+        version: Option<String>,
+    },
+    /// A git repository, at a tag, branch or commit or its default branch
+    Git { url: String, rev: Option<String> },
+    /// A local directory
+    Path(PathBuf),
+}
+
+impl PluginSource {
+    /// Parse `<name|git-url|path>[@version]`
+    ///
+    /// Local directories have to start with `./` or `../` or be absolute, so a directory that
+    /// happens to be named like a plugin of the index doesn't get installed instead of it.
+    pub fn parse(spec: &str) -> Self {
+        let path = Path::new(spec);
+        if path.is_absolute() || path.starts_with(".") || path.starts_with("..") {
+            return Self::Path(path.to_path_buf());
+        }
+
+        // `git@host:org/repo` has an `@` that isn't a version
+        let (base, version) = match spec.rsplit_once('@') {
+            Some((base, version))
+                if !base.is_empty() && !version.contains(['/', ':']) && base != "git" =>
+            {
+                (base, Some(version.to_string()))
+            }
+            _ => (spec, None),
+        };
+
+        let is_git = ["http://", "https://", "ssh://", "git@"]
+            .iter()
+            .any(|prefix| base.starts_with(prefix))
+            || base.ends_with(".git");
+        if is_git {
+            Self::Git {
+                url: base.to_string(),
+                rev: version,
+            }
+        } else {
+            Self::Index {
+                name: base.to_string(),
+                version,
+            }
+        }
+    }
+
+    /// The directory name the plugin is installed under
+    fn name(&self) -> anyhow::Result<String> {
+        let name = match self {
+            Self::Index { name, .. } => name.clone(),
+            Self::Git { url, .. } => url
+                .trim_end_matches('/')
+                .trim_end_matches(".git")
+                .rsplit(['/', ':'])
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            Self::Path(path) => path
+                .canonicalize()?
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or_default()
+                .to_string(),
+        };
+        validate_name(&name)?;
+        Ok(name)
+    }
+}
+
+/// What gets written into [`INSTALL_FILE`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledPlugin {
+    pub name: String,
+    /// `index`, `git` or `path`
+    pub source: String,
+    /// The repository url or directory, for git and path installs
+    pub location: Option<String>,
+    pub version: Option<String>,
+    pub rev: Option<String>,
+    /// The commit that was checked out, for index and git installs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    pub checksum: String,
+}
+
+impl InstalledPlugin {
+    pub fn load(plugin_dir: &Path) -> Option<Self> {
+        let text = std::fs::read_to_string(plugin_dir.join(INSTALL_FILE)).ok()?;
+        toml::from_str(&text).ok()
+    }
+
+    /// The source to update the plugin from, following the latest version for index plugins
+    fn update_source(&self) -> PluginSource {
+        match self.source.as_str() {
+            "git" => PluginSource::Git {
+                url: self.location.clone().unwrap_or_default(),
+                rev: self.rev.clone(),
+            },
+            "path" => PluginSource::Path(PathBuf::from(self.location.clone().unwrap_or_default())),
+            _ => PluginSource::Index {
+                name: self.name.clone(),
+                version: None,
+            },
+        }
+    }
+
+    /// The source to get exactly this install again
+    pub fn exact_source(&self) -> PluginSource {
+        match (&self.location, &self.commit) {
+            (Some(url), Some(commit)) if self.source != "path" => PluginSource::Git {
+                url: url.clone(),
+                rev: Some(commit.clone()),
+            },
+            _ => self.update_source(),
+        }
+    }
+}
+
+/// The directory every plugin is installed into
+pub fn plugins_dir() -> PathBuf {
+    let dir = app_path().join("plugins");
+    let _ = std::fs::create_dir_all(&dir);
+    dir
+}
+
+/// Install the plugin described by `spec`, replacing an installed plugin of the same name
+pub async fn add(spec: &str, index: Option<&str>) -> anyhow::Result<InstalledPlugin> {
+    install(PluginSource::parse(spec), index).await
+}
+
+/// Update one plugin, or all installed plugins if `name` is `None`
+pub async fn update(name: Option<&str>, index: Option<&str>) -> anyhow::Result<()> {
+    let installed = match name {
+        Some(name) => vec![InstalledPlugin::load(&plugin_path(name)?).ok_or_else(|| {
+            anyhow!("Plugin `{name}` wasn't installed with `dioxus plugin add`.")
+        })?],
+        None => installed_plugins(),
+    };
+
+    for plugin in installed {
+        let updated = install(plugin.update_source(), index).await?;
+        match (&plugin.version, &updated.version) {
+            (Some(old), Some(new)) if old != new => {
+                log::info!("⬆️  Updated `{}` from {old} to {new}", plugin.name)
+            }
+            _ => log::info!("⬆️  Updated `{}`", plugin.name),
+        }
+    }
+    Ok(())
+}
+
+pub fn remove(name: &str) -> anyhow::Result<()> {
+    let dir = plugin_path(name)?;
+    if !dir.is_dir() {
+        return Err(anyhow!("Plugin `{name}` isn't installed."));
+    }
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+/// Every plugin installed with `dioxus plugin add`
+pub fn installed_plugins() -> Vec<InstalledPlugin> {
+    let Ok(entries) = std::fs::read_dir(plugins_dir()) else {
+        return vec![];
+    };
+    let mut plugins: Vec<InstalledPlugin> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| InstalledPlugin::load(&entry.path()))
+        .collect();
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+    plugins
+}
+
+pub fn plugin_path(name: &str) -> anyhow::Result<PathBuf> {
+    validate_name(name)?;
+    Ok(plugins_dir().join(name))
+}
+
+fn validate_name(name: &str) -> anyhow::Result<()> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(anyhow!("Invalid plugin name `{name}`."));
+    }
+    Ok(())
+}
+
+async fn install(source: PluginSource, index: Option<&str>) -> anyhow::Result<InstalledPlugin> {
+    let name = source.name()?;
+    let target = plugin_path(&name)?;
+    install_into(&source, &name, index, &target).await
+}
+
+/// Install the plugin from `source` as `name` into the `target` directory
+pub async fn install_into(
+    source: &PluginSource,
+    name: &str,
+    index: Option<&str>,
+    target: &Path,
+) -> anyhow::Result<InstalledPlugin> {
+    let parent = target
+        .parent()
+        .ok_or_else(|| anyhow!("Invalid plugin directory `{}`.", target.display()))?;
+    std::fs::create_dir_all(parent)?;
+
+    // fetch into a temporary directory first, so a failed install keeps the old version
+    let staging = staging_dir(parent, &format!(".{name}"));
+
+    let result = fetch_plugin(source, name, &staging, index).await;
+    let installed = match result {
+        Ok(installed) => installed,
+        Err(err) => {
+            let _ = std::fs::remove_dir_all(&staging);
+            return Err(err);
+        }
+    };
+
+    std::fs::write(
+        staging.join(INSTALL_FILE),
+        toml::to_string_pretty(&installed)?,
+    )?;
+    if target.exists() {
+        std::fs::remove_dir_all(target)?;
+    }
+    std::fs::rename(&staging, target)?;
+
+    Ok(installed)
+}
+
+/// A new directory name in `parent` to fetch a plugin into before moving it in place
+///
+/// It's unique, so installs running at the same time don't write into each other's files.
+pub fn staging_dir(parent: &Path, prefix: &str) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos());
+    parent.join(format!("{prefix}-{}-{nanos}.tmp", std::process::id()))
+}
+
+async fn fetch_plugin(
+    source: &PluginSource,
+    name: &str,
+    staging: &Path,
+    index: Option<&str>,
+) -> anyhow::Result<InstalledPlugin> {
+    match source {
+        PluginSource::Index { name, version } => {
+            let index = PluginIndex::load(index).await?;
+            let entry = index
+                .find(name)
+                .ok_or_else(|| anyhow!("Plugin `{name}` isn't in the plugin index."))?;
+            let release = entry.get_version(version.as_deref()).ok_or_else(|| {
+                anyhow!(
+                    "Plugin `{name}` has no version `{}`.",
+                    version.as_deref().unwrap_or("latest")
+                )
+            })?;
+
+            log::info!("📦 Installing {name} v{}", release.version);
+            clone_at(&entry.repository, release.rev.as_deref(), staging)?;
+
+            let checksum = dir_checksum(staging)?;
+            if let Some(expected) = &release.checksum {
+                if *expected != checksum {
+                    return Err(anyhow!(
+                        "Checksum mismatch for `{name}` v{}: expected {expected}, got {checksum}.",
+                        release.version
+                    ));
+                }
+            }
+
+            Ok(InstalledPlugin {
+                name: name.clone(),
+                source: "index".into(),
+                location: Some(entry.repository.clone()),
+                version: Some(release.version.clone()),
+                rev: release.rev.clone(),
+                commit: head_commit(staging),
+                checksum,
+            })
+        }
+        PluginSource::Git { url, rev } => {
+            log::info!("📦 Installing {name} from {url}");
+            clone_at(url, rev.as_deref(), staging)?;
+            Ok(InstalledPlugin {
+                name: name.to_string(),
+                source: "git".into(),
+                location: Some(url.clone()),
+                version: None,
+                rev: rev.clone(),
+                commit: head_commit(staging),
+                checksum: dir_checksum(staging)?,
+            })
+        }
+        PluginSource::Path(path) => {
+            log::info!("📦 Installing {name} from {}", path.display());
+            let path = path.canonicalize()?;
+            std::fs::create_dir_all(staging)?;
+            let options = fs_extra::dir::CopyOptions {
+                content_only: true,
+                ..Default::default()
+            };
+            fs_extra::dir::copy(&path, staging, &options)?;
+            Ok(InstalledPlugin {
+                name: name.to_string(),
+                source: "path".into(),
+                location: Some(path.to_string_lossy().to_string()),
+                version: None,
+                rev: None,
+                commit: None,
+                checksum: dir_checksum(staging)?,
+            })
+        }
+    }
+}
+
+fn clone_at(url: &str, rev: Option<&str>, dir: &Path) -> anyhow::Result<()> {
+    clone_repo(dir, url)?;
+    if !dir.join(".git").is_dir() {
+        return Err(anyhow!("Failed to clone `{url}`."));
+    }
+    if let Some(rev) = rev {
+        let status = Command::new("git")
+            .current_dir(dir)
+            .args(["checkout", "--quiet", rev])
+            .status()?;
+        if !status.success() {
+            return Err(anyhow!("`{url}` has no tag, branch or commit `{rev}`."));
+        }
+    }
+    Ok(())
+}
+
+fn head_commit(dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The sha256 of the plugin files, as `sha256:<hex>`
+///
+/// Every file except the git metadata and the files the CLI writes itself is hashed with its
+/// path relative to `dir`, in path order, so the checksum is the same on every system.
+pub fn dir_checksum(dir: &Path) -> anyhow::Result<String> {
+    let mut files: Vec<(String, PathBuf)> = walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| {
+            let name = entry.file_name();
+            name != "dcp.json" && name != INSTALL_FILE
+        })
+        .map(|entry| {
+            let relative = entry.path().strip_prefix(dir).unwrap();
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            (relative, entry.into_path())
+        })
+        .collect();
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for (relative, path) in files {
+        hasher.update(relative.as_bytes());
+        hasher.update([0]);
+        hasher.update(std::fs::read(path)?);
+        hasher.update([0]);
+    }
+    Ok(format!("sha256:{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_sources() {
+        assert_eq!(
+            PluginSource::parse("tailwind"),
+            PluginSource::Index {
+                name: "tailwind".into(),
+                version: None
+            }
+        );
+        assert_eq!(
+            PluginSource::parse("tailwind@0.2.0"),
+            PluginSource::Index {
+                name: "tailwind".into(),
+                version: Some("0.2.0".into())
+            }
+        );
+        assert_eq!(
+            PluginSource::parse("https://github.com/user/plugin.git@v1"),
+            PluginSource::Git {
+                url: "https://github.com/user/plugin.git".into(),
+                rev: Some("v1".into())
+            }
+        );
+        assert_eq!(
+            PluginSource::parse("git@github.com:user/plugin.git"),
+            PluginSource::Git {
+                url: "git@github.com:user/plugin.git".into(),
+                rev: None
+            }
+        );
+        assert_eq!(
+            PluginSource::parse("./plugins/tailwind"),
+            PluginSource::Path("./plugins/tailwind".into())
+        );
+        assert_eq!(
+            PluginSource::parse("../tailwind"),
+            PluginSource::Path("../tailwind".into())
+        );
+        let absolute = std::env::temp_dir().join("tailwind");
+        assert_eq!(
+            PluginSource::parse(absolute.to_str().unwrap()),
+            PluginSource::Path(absolute)
+        );
+        // `src` is a directory of this crate, but only explicit paths are local
+        assert_eq!(
+            PluginSource::parse("src"),
+            PluginSource::Index {
+                name: "src".into(),
+                version: None
+            }
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! Dev server routes and middleware registered by plugins
+//!
+//! ```lua
+//! manager.serve.routes = {
+//!     ["/api/login"] = function (req)
+//!         if req.method ~= "POST" then
+//!             return { status = 405 }
+//!         end
+//!         return {
+//!             headers = { ["content-type"] = "application/json" },
+//!             body = "{\"token\": \"dev\"}",
+//!         }
+//!     end,
+//! }
+//!
+//! manager.serve.middleware = function (req, res)
+//!     res.headers["x-served-by"] = "dioxus"
+//!     return res
+//! end
+//! ```
+use hyper::http::{HeaderMap, HeaderName, HeaderValue};
+use mlua::{FromLua, Lua, Table};
+
+/// A request to the dev server, as plugins see it
+#[derive(Debug, Clone, Default)]
+pub struct PluginRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: HeaderMap,
+    /// Empty for middleware, the body belongs to the route handling the request
+    pub body: Vec<u8>,
+}
+
+impl PluginRequest {
+    pub fn to_table<'lua>(&self, lua: &'lua Lua) -> mlua::Result<Table<'lua>> {
+        let res = lua.create_table()?;
+        res.set("method", self.method.clone())?;
+        res.set("path", self.path.clone())?;
+        res.set("query", self.query.clone())?;
+        res.set("headers", headers_to_table(lua, &self.headers)?)?;
+        res.set("body", lua.create_string(&self.body)?)?;
+        Ok(res)
+    }
+}
+
+/// A response of a plugin route, or of the dev server given to middleware
+#[derive(Debug, Clone)]
+pub struct PluginResponse {
+    pub status: u16,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl Default for PluginResponse {
+    fn default() -> Self {
+        Self {
+            status: 200,
+            headers: HeaderMap::new(),
+            body: vec![],
+        }
+    }
+}
+
+impl PluginResponse {
+    pub fn error(message: String) -> Self {
+        Self {
+            status: 500,
+            body: message.into_bytes(),
+            ..Default::default()
+        }
+    }
+
+    pub fn to_table<'lua>(&self, lua: &'lua Lua) -> mlua::Result<Table<'lua>> {
+        let res = lua.create_table()?;
+        res.set("status", self.status)?;
+        res.set("headers", headers_to_table(lua, &self.headers)?)?;
+        res.set("body", lua.create_string(&self.body)?)?;
+        Ok(res)
+    }
+}
+
+/// A handler can return a whole response table, or just the body as a string
+impl<'lua> FromLua<'lua> for PluginResponse {
+    fn from_lua(lua_value: mlua::Value<'lua>, lua: &'lua Lua) -> mlua::Result<Self> {
+        let mut res = Self::default();
+        match lua_value {
+            mlua::Value::String(body) => res.body = body.as_bytes().to_vec(),
+            mlua::Value::Table(tab) => {
+                if let Ok(v) = tab.get::<_, u16>("status") {
+                    res.status = v;
+                }
+                if let Some(headers) = tab.get::<_, Option<Table>>("headers")? {
+                    res.headers = headers_from_table(lua, headers)?;
+                }
+                if let Ok(v) = tab.get::<_, mlua::String>("body") {
+                    res.body = v.as_bytes().to_vec();
+                }
+            }
+            other => {
+                return Err(mlua::Error::FromLuaConversionError {
+                    from: other.type_name(),
+                    to: "response",
+                    message: Some("expected a table or a string".into()),
+                })
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// Header names are lowercase, repeated headers like `set-cookie` become a list of values
+fn headers_to_table<'lua>(lua: &'lua Lua, headers: &HeaderMap) -> mlua::Result<Table<'lua>> {
+    let res = lua.create_table()?;
+    for name in headers.keys() {
+        let mut values = headers
+            .get_all(name)
+            .iter()
+            .map(|value| lua.create_string(value.as_bytes()))
+            .collect::<mlua::Result<Vec<_>>>()?;
+        if values.len() == 1 {
+            res.set(name.as_str(), values.remove(0))?;
+        } else {
+            res.set(name.as_str(), values)?;
+        }
+    }
+    Ok(res)
+}
+
+fn headers_from_table<'lua>(lua: &'lua Lua, tab: Table<'lua>) -> mlua::Result<HeaderMap> {
+    let invalid = |message: String| mlua::Error::FromLuaConversionError {
+        from: "table",
+        to: "headers",
+        message: Some(message),
+    };
+
+    let mut headers = HeaderMap::new();
+    for pair in tab.pairs::<mlua::String, mlua::Value>() {
+        let (name, value) = pair?;
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| invalid(format!("invalid header name `{}`", name.to_string_lossy())))?;
+        let values = match value {
+            mlua::Value::Table(list) => list
+                .sequence_values::<mlua::String>()
+                .collect::<mlua::Result<Vec<_>>>()?,
+            value => vec![mlua::String::from_lua(value, lua)?],
+        };
+        for value in values {
+            let value = HeaderValue::from_bytes(value.as_bytes())
+                .map_err(|_| invalid(format!("invalid value for header `{name}`")))?;
+            headers.append(&name, value);
+        }
+    }
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn headers_round_trip() {
+        let lua = Lua::new();
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("text/html"));
+        headers.append("set-cookie", HeaderValue::from_static("a=1"));
+        headers.append("set-cookie", HeaderValue::from_static("b=2"));
+        headers.insert("x-raw", HeaderValue::from_bytes(b"caf\xe9").unwrap());
+
+        let table = headers_to_table(&lua, &headers).unwrap();
+        assert_eq!(table.get::<_, String>("content-type").unwrap(), "text/html");
+        assert_eq!(
+            table.get::<_, Vec<String>>("set-cookie").unwrap(),
+            ["a=1", "b=2"]
+        );
+        assert_eq!(headers_from_table(&lua, table).unwrap(), headers);
+    }
+
+    #[test]
+    fn responses_from_lua() {
+        let lua = Lua::new();
+        let response: PluginResponse = lua
+            .load(r#"return { status = 201, headers = { ["X-Id"] = "1" }, body = "created" }"#)
+            .eval()
+            .unwrap();
+        assert_eq!(response.status, 201);
+        assert_eq!(response.headers["x-id"], "1");
+        assert_eq!(response.body, b"created");
+
+        let response: PluginResponse = lua.load(r#"return "hello""#).eval().unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"hello");
+
+        let invalid = lua.load(r#"return { headers = { ["bad header"] = "1" } }"#);
+        assert!(invalid.eval::<PluginResponse>().is_err());
+        assert!(lua.load("return 42").eval::<PluginResponse>().is_err());
+    }
+}
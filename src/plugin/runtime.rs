@@ -0,0 +1,37 @@
+//! Running plugins on the tokio runtime
+//!
+//! Every call into a plugin runs as a coroutine driven by [`block_on`], so the `plugin_lib`
+//! functions doing IO are async: while a plugin waits for a command or a download, the runtime
+//! keeps serving requests and watching files.
+use std::{future::Future, sync::Mutex};
+use tokio::runtime::{Builder, Handle};
+
+lazy_static::lazy_static! {
+    /// The runtime of the CLI, for plugin calls made from threads outside of it like the file
+    /// watcher
+    static ref HANDLE: Mutex<Option<Handle>> = Mutex::new(None);
+}
+
+/// Remember the current runtime, must be called from inside of it
+pub fn init() {
+    *HANDLE.lock().unwrap() = Handle::try_current().ok();
+}
+
+/// Run `future` to completion from synchronous code, on whatever thread that is
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    match Handle::try_current() {
+        // a worker thread, which is handed over to the other tasks while this one blocks
+        Ok(handle) => tokio::task::block_in_place(|| handle.block_on(future)),
+        Err(_) => {
+            let handle = HANDLE.lock().unwrap().clone();
+            match handle {
+                Some(handle) => handle.block_on(future),
+                None => Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("failed to start a runtime for plugins")
+                    .block_on(future),
+            }
+        }
+    }
+}
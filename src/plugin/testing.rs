@@ -0,0 +1,327 @@
+//! `dioxus plugin test`, checking a plugin without a real project
+//!
+//! Every test loads the plugin in a fresh Lua state, against a fixture project in a temporary
+//! directory, with `command` and `network` mocked by `assets/plugin-test.lua`. Two kinds of tests
+//! are run:
+//!
+//! - `hooks::<hook>`: every hook the plugin has, called with fixture arguments, which passes
+//!   unless the hook raises an error or returns `false`
+//! - `<file>::<name>`: the functions in the table returned by each `tests/*.lua` file
+use super::{
+    commands::PluginCommand,
+    interface::{PluginInfo, PluginInner},
+    permissions::{self, Sandbox},
+    runtime,
+    types::PluginConfig,
+    HookOutput, LuaPlugin,
+};
+use crate::DioxusConfig;
+use colored::Colorize;
+use mlua::{Function, Lua, Table};
+use std::{
+    io::Write,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+/// The mocks and assertion helpers of the `test` global
+const PRELUDE: &str = include_str!("../assets/plugin-test.lua");
+
+/// The hooks called with fixture arguments when the plugin has them
+const HOOKS: &[&str] = &[
+    "on_init",
+    "build.on_start",
+    "build.after_cargo",
+    "build.after_bindgen",
+    "build.after_wasm_opt",
+    "build.transform_asset",
+    "build.transform_html",
+    "build.on_finish",
+    "serve.on_start",
+    "serve.on_rebuild",
+    "serve.on_shutdown",
+];
+
+enum Test {
+    Hook(&'static str),
+    Case {
+        file: PathBuf,
+        name: String,
+    },
+    /// A test file that couldn't be loaded
+    Broken {
+        file: PathBuf,
+        error: String,
+    },
+}
+
+impl Test {
+    fn name(&self) -> String {
+        match self {
+            Test::Hook(hook) => format!("hooks::{hook}"),
+            Test::Case { file, name } => format!("{}::{name}", file_stem(file)),
+            Test::Broken { file, .. } => file_stem(file),
+        }
+    }
+}
+
+fn file_stem(file: &Path) -> String {
+    file.file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Run the tests of the plugin in `plugin_dir` whose name contains `filter`, printing the
+/// results like `cargo test`, and return how many failed
+pub fn run(plugin_dir: &Path, filter: Option<&str>) -> anyhow::Result<usize> {
+    if !plugin_dir.join("init.lua").is_file() {
+        return Err(anyhow::anyhow!(
+            "`{}` isn't a plugin, it has no `init.lua`.",
+            plugin_dir.display()
+        ));
+    }
+    let fixtures = std::env::temp_dir().join(format!("dioxus-plugin-test-{}", std::process::id()));
+    let result = run_in(plugin_dir, &fixtures, filter);
+    let _ = std::fs::remove_dir_all(&fixtures);
+    result
+}
+
+fn run_in(plugin_dir: &Path, fixtures: &Path, filter: Option<&str>) -> anyhow::Result<usize> {
+    let start = Instant::now();
+    let all = discover(plugin_dir, &fixtures.join("discover"))?;
+    let total = all.len();
+    let tests: Vec<Test> = all
+        .into_iter()
+        .filter(|test| filter.map_or(true, |filter| test.name().contains(filter)))
+        .collect();
+
+    println!("\nrunning {} tests", tests.len());
+    let mut failures = vec![];
+    for (index, test) in tests.iter().enumerate() {
+        print!("test {} ... ", test.name());
+        let _ = std::io::stdout().flush();
+        match run_test(test, plugin_dir, &fixtures.join(index.to_string())) {
+            Ok(()) => println!("{}", "ok".green()),
+            Err(error) => {
+                println!("{}", "FAILED".red());
+                failures.push((test.name(), error));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("\nfailures:\n");
+        for (name, error) in &failures {
+            println!("---- {name} ----\n{error}\n");
+        }
+        println!("failures:");
+        for (name, _) in &failures {
+            println!("    {name}");
+        }
+    }
+
+    let result = if failures.is_empty() {
+        "ok".green()
+    } else {
+        "FAILED".red()
+    };
+    println!(
+        "\ntest result: {result}. {} passed; {} failed; {} filtered out; finished in {:.2}s\n",
+        tests.len() - failures.len(),
+        failures.len(),
+        total - tests.len(),
+        start.elapsed().as_secs_f64()
+    );
+    Ok(failures.len())
+}
+
+/// The hooks of the plugin and the cases of its `tests/*.lua` files
+fn discover(plugin_dir: &Path, fixture: &Path) -> anyhow::Result<Vec<Test>> {
+    let plugin =
+        load(plugin_dir, fixture).map_err(|e| anyhow::anyhow!("Failed to load the plugin: {e}"))?;
+    let find_hook = plugin
+        .lua
+        .globals()
+        .get::<_, Table>("test")?
+        .get::<_, Function>("find_hook")?;
+
+    let mut tests = vec![];
+    for hook in HOOKS {
+        if find_hook.call::<_, Option<Function>>(*hook)?.is_some() {
+            tests.push(Test::Hook(hook));
+        }
+    }
+
+    let mut files: Vec<PathBuf> = std::fs::read_dir(plugin_dir.join("tests"))
+        .map(|dir| {
+            dir.filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().map_or(false, |ext| ext == "lua"))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+
+    for file in files {
+        let cases = load_cases(&plugin.lua, &file).and_then(|cases| {
+            let mut names = vec![];
+            for pair in cases.pairs::<String, Function>() {
+                names.push(pair?.0);
+            }
+            Ok(names)
+        });
+        match cases {
+            Ok(mut names) => {
+                names.sort();
+                for name in names {
+                    let file = file.clone();
+                    tests.push(Test::Case { file, name });
+                }
+            }
+            Err(e) => tests.push(Test::Broken {
+                file,
+                error: e.to_string(),
+            }),
+        }
+    }
+    Ok(tests)
+}
+
+fn run_test(test: &Test, plugin_dir: &Path, fixture: &Path) -> Result<(), String> {
+    if let Test::Broken { error, .. } = test {
+        return Err(error.clone());
+    }
+
+    let run = || -> mlua::Result<HookOutput> {
+        let plugin = load(plugin_dir, fixture)?;
+        let lua = &plugin.lua;
+        let func = match test {
+            Test::Hook(hook) => {
+                let call_hook = lua
+                    .globals()
+                    .get::<_, Table>("test")?
+                    .get::<_, Function>("call_hook")?;
+                call_hook.bind(*hook)?
+            }
+            Test::Case { file, name } => {
+                load_cases(lua, file)?.get::<_, Function>(name.as_str())?
+            }
+            Test::Broken { .. } => unreachable!(),
+        };
+        LuaPlugin::reset_budget(lua, plugin.instruction_limit);
+        runtime::block_on(func.call_async::<_, HookOutput>(()))
+    };
+
+    let result = match catch_unwind(AssertUnwindSafe(run)) {
+        Ok(Ok(HookOutput::Fail(message))) => Err(format!(
+            "returned false: {}",
+            message.unwrap_or_else(|| "no reason given".into())
+        )),
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("the test panicked".into()),
+    };
+    let _ = std::fs::remove_dir_all(fixture);
+    result
+}
+
+/// Load the plugin against a fixture project in `fixture`, with the test helpers
+fn load(plugin_dir: &Path, fixture: &Path) -> mlua::Result<LuaPlugin> {
+    let out_dir = fixture.join("dist");
+    let asset_dir = fixture.join("public");
+    // the installed plugins stay out of reach of the tests
+    let library_dir = fixture.join("plugins");
+    for dir in [&out_dir, &asset_dir, &library_dir] {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let config = PluginConfig::from_toml_value(toml::Value::Table(Default::default()));
+    let lua = LuaPlugin::create_state(&config, &library_dir, plugin_dir, true)?;
+    install_helpers(&lua)?;
+
+    let dioxus_config = DioxusConfig::default();
+    let args = lua.create_table()?;
+    args.set("name", dioxus_config.application.name)?;
+    args.set("platform", "web")?;
+    args.set("crate_dir", fixture.to_str().unwrap())?;
+    args.set("out_dir", out_dir.to_str().unwrap())?;
+    args.set("asset_dir", asset_dir.to_str().unwrap())?;
+    lua.globals()
+        .get::<_, Table>("test")?
+        .set("fixture", args)?;
+
+    LuaPlugin::reset_budget(&lua, config.instruction_limit);
+    let source = std::fs::read_to_string(plugin_dir.join("init.lua"))?;
+    let chunk = lua.load(&source).set_name("@init.lua")?;
+    let manager = runtime::block_on(chunk.eval_async::<Table>())?;
+    lua.globals().set("manager", manager.clone())?;
+    let inner = PluginInner {
+        plugin_dir: plugin_dir.to_str().unwrap().to_string(),
+        from_loader: true,
+    };
+    manager.set("inner", inner)?;
+
+    // permissions are granted without asking, everything happens in the fixture
+    let info = lua.globals().get::<_, PluginInfo>("manager")?;
+    let sandbox = Sandbox::new(&info.permissions, plugin_dir, Some(fixture), Some(&out_dir));
+    permissions::apply(&lua, sandbox);
+
+    let name = info.name.clone();
+    let commands = PluginCommand::from_manager(&manager);
+    drop((info, manager));
+    Ok(LuaPlugin {
+        lua,
+        name,
+        from_loader: true,
+        instruction_limit: config.instruction_limit,
+        commands,
+        plugin_dir: plugin_dir.to_path_buf(),
+        config,
+    })
+}
+
+/// Add the `test` global and replace `command` and `network` with mocks
+fn install_helpers(lua: &Lua) -> mlua::Result<()> {
+    lua.load(PRELUDE).set_name("@plugin-test.lua")?.exec()
+}
+
+/// The table of test functions returned by a `tests/*.lua` file
+fn load_cases<'lua>(lua: &'lua Lua, file: &Path) -> mlua::Result<Table<'lua>> {
+    let source = std::fs::read_to_string(file)?;
+    let name = format!("@tests/{}", file.file_name().unwrap().to_string_lossy());
+    runtime::block_on(lua.load(&source).set_name(&name)?.eval_async::<Table>())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn helpers_assert_and_mock() {
+        let lua = Lua::new();
+        lua.globals()
+            .set("plugin_lib", lua.create_table().unwrap())
+            .unwrap();
+        install_helpers(&lua).unwrap();
+
+        let run = |code: &str| lua.load(code).exec();
+        assert!(run("test.assert_eq({ 1, { a = 'b' } }, { 1, { a = 'b' } })").is_ok());
+        assert!(run("test.assert_eq({ 1, 2 }, { 1 })").is_err());
+        assert!(run("test.assert_error(function () error('boom') end, 'boom')").is_ok());
+
+        run(r#"
+            test.mock_command("git", { code = 1, stderr = "not a repository" })
+            local output = plugin_lib.command.exec({ "git", "status" })
+            test.assert_eq(output.code, 1)
+            test.assert(not output.success)
+            test.assert_eq(test.commands, { { "git", "status" } })
+            test.assert_eq(output.stderr, "not a repository")
+
+            local inherited = plugin_lib.command.exec({ "git", "status" }, "inherit", "null")
+            test.assert_eq(inherited.stderr, "")
+        "#)
+        .unwrap();
+    }
+}
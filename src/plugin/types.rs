@@ -1,14 +1,62 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use mlua::ToLua;
 
+/// Roughly a few seconds of Lua, enough for any hook that isn't stuck in a loop
+pub const DEFAULT_INSTRUCTION_LIMIT: u64 = 1_000_000_000;
+
 #[derive(Debug, Clone)]
 pub struct PluginConfig {
     pub available: bool,
     pub loader: Vec<String>,
+    /// How many Lua instructions one call into a plugin may run, `0` for no limit
+    pub instruction_limit: u64,
+    /// The plugins of `[plugin.dependencies]`, by name
+    pub dependencies: BTreeMap<String, PluginDependency>,
     pub config_info: HashMap<String, HashMap<String, Value>>,
 }
 
+/// A plugin the project depends on
+///
+/// Either a version requirement like `tailwind = "0.1"`, or a table with a `git` url and an
+/// optional `rev`, or a local `path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PluginDependency {
+    Index { version: String },
+    Git { url: String, rev: Option<String> },
+    Path(String),
+}
+
+impl PluginDependency {
+    pub fn from_toml(name: &str, value: &toml::Value) -> Option<Self> {
+        let dependency = match value {
+            toml::Value::String(version) => Self::Index {
+                version: version.clone(),
+            },
+            toml::Value::Table(table) => {
+                let get = |key: &str| table.get(key).and_then(|v| v.as_str()).map(String::from);
+                if let Some(url) = get("git") {
+                    Self::Git {
+                        url,
+                        rev: get("rev"),
+                    }
+                } else if let Some(path) = get("path") {
+                    Self::Path(path)
+                } else {
+                    Self::Index {
+                        version: get("version").unwrap_or_else(|| "*".into()),
+                    }
+                }
+            }
+            _ => {
+                log::warn!("Invalid plugin dependency `{name}`, expected a version or a table.");
+                return None;
+            }
+        };
+        Some(dependency)
+    }
+}
+
 impl<'lua> ToLua<'lua> for PluginConfig {
     fn to_lua(self, lua: &'lua mlua::Lua) -> mlua::Result<mlua::Value<'lua>> {
         let table = lua.create_table()?;
@@ -45,10 +93,27 @@ impl PluginConfig {
                 }
             }
 
+            let instruction_limit = tab
+                .get("instruction_limit")
+                .and_then(|v| v.as_integer())
+                .map(|v| v.max(0) as u64)
+                .unwrap_or(DEFAULT_INSTRUCTION_LIMIT);
+
+            let mut dependencies = BTreeMap::new();
+            if let Some(toml::Value::Table(deps)) = tab.get("dependencies") {
+                for (name, value) in deps {
+                    if let Some(dependency) = PluginDependency::from_toml(name, value) {
+                        dependencies.insert(name.clone(), dependency);
+                    }
+                }
+            }
+
             let mut config_info = HashMap::new();
 
             for (name, value) in tab {
-                if name == "available" || name == "loader" {
+                if ["available", "loader", "instruction_limit", "dependencies"]
+                    .contains(&name.as_str())
+                {
                     continue;
                 }
                 if let toml::Value::Table(value) = value {
@@ -63,12 +128,16 @@ impl PluginConfig {
             Self {
                 available,
                 loader,
+                instruction_limit,
+                dependencies,
                 config_info,
             }
         } else {
             Self {
                 available: false,
                 loader: vec![],
+                instruction_limit: DEFAULT_INSTRUCTION_LIMIT,
+                dependencies: BTreeMap::new(),
                 config_info: HashMap::new(),
             }
         }
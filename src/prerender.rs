@@ -0,0 +1,206 @@
+//! Static site generation: render routes to HTML at build time
+//!
+//! The CLI can't render the app itself, so it builds the crate for the host and asks the binary
+//! to render each route. The app opts in by checking the environment when it starts:
+//!
+//! - `DIOXUS_PRERENDER=list` prints a JSON array of the routes it wants prerendered
+//! - `DIOXUS_PRERENDER=render` with `DIOXUS_PRERENDER_ROUTE=/some/route` prints the
+//!   hydration-ready markup of that route (for example from `dioxus_ssr::pre_render`)
+use crate::{
+    config::{CrateConfig, ExecutableType},
+    error::{Error, Result},
+};
+use std::{
+    path::{Component, Path, PathBuf},
+    process::Command,
+};
+
+pub const PRERENDER_ENV: &str = "DIOXUS_PRERENDER";
+pub const PRERENDER_ROUTE_ENV: &str = "DIOXUS_PRERENDER_ROUTE";
+
+/// Render every configured route into `out_dir/<route>/index.html`
+///
+/// `index_html` is the page produced by `gen_page`; the rendered markup is placed inside its
+/// `<div id="main">` so the wasm bootstrap can hydrate it.
+pub fn prerender(config: &CrateConfig, index_html: &str) -> Result<()> {
+    let prerender_config = config
+        .dioxus_config
+        .web
+        .prerender
+        .clone()
+        .unwrap_or_default();
+
+    let binary = build_host(config, &prerender_config.features.unwrap_or_default())?;
+
+    let mut routes = prerender_config.routes.unwrap_or_default();
+    if prerender_config.discover.unwrap_or(false) {
+        routes.extend(discover_routes(config, &binary)?);
+    }
+    if routes.is_empty() {
+        routes.push("/".into());
+    }
+    routes.sort();
+    routes.dedup();
+
+    for route in routes {
+        log::info!("📄 Prerendering {route}");
+
+        let output = Command::new(&binary)
+            .current_dir(&config.crate_dir)
+            .env(PRERENDER_ENV, "render")
+            .env(PRERENDER_ROUTE_ENV, &route)
+            .output()?;
+        if !output.status.success() {
+            return Err(Error::BuildFailed(format!(
+                "Prerendering `{route}` failed:\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+        let markup = String::from_utf8_lossy(&output.stdout);
+
+        let Some(page) = insert_markup(index_html, markup.trim()) else {
+            return Err(Error::BuildFailed(
+                "Prerendering needs a `<div id=\"main\"></div>` element in the index page.".into(),
+            ));
+        };
+
+        let target = config.out_dir.join(route_path(&route)?).join("index.html");
+        std::fs::create_dir_all(target.parent().unwrap())?;
+        std::fs::write(target, page)?;
+    }
+
+    Ok(())
+}
+
+/// Build the crate for the host so it can be executed by the CLI
+fn build_host(config: &CrateConfig, extra_features: &[String]) -> Result<PathBuf> {
+    let (target_arg, name) = match &config.executable {
+        ExecutableType::Binary(name) => ("--bin", name),
+        ExecutableType::Example(name) => ("--example", name),
+        // a library has no binary the CLI could run
+        ExecutableType::Lib(name) => {
+            return Err(Error::BuildFailed(format!(
+                "Prerendering needs a binary or example target, `{name}` is a library."
+            )))
+        }
+    };
+
+    log::info!("🚅 Running build [Prerender] command...");
+
+    let mut cmd = Command::new("cargo");
+    cmd.current_dir(&config.crate_dir).arg("build");
+
+    if config.release {
+        cmd.arg("--release");
+    }
+    if config.verbose {
+        cmd.arg("--verbose");
+    }
+
+    if let Some(custom_profile) = &config.custom_profile {
+        cmd.arg("--profile");
+        cmd.arg(custom_profile);
+    }
+
+    let mut features = config.features.clone().unwrap_or_default();
+    features.extend(extra_features.iter().cloned());
+    if !features.is_empty() {
+        cmd.arg("--features");
+        cmd.arg(features.join(" "));
+    }
+
+    cmd.arg(target_arg).arg(name);
+
+    let output = cmd.output()?;
+    if !output.status.success() {
+        let message = format!(
+            "Prerender build failed.\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(Error::BuildFailed(message.trim_end().to_string()));
+    }
+
+    let release_type = match config.release {
+        true => "release",
+        false => "debug",
+    };
+    let mut binary = config.target_dir.join(release_type);
+    if let ExecutableType::Example(_) = &config.executable {
+        binary.push("examples");
+    }
+    binary.push(name);
+    if cfg!(windows) {
+        binary.set_extension("exe");
+    }
+
+    Ok(binary)
+}
+
+/// Ask the app for its routes, from the same directory the routes are rendered in
+fn discover_routes(config: &CrateConfig, binary: &Path) -> Result<Vec<String>> {
+    let output = Command::new(binary)
+        .current_dir(&config.crate_dir)
+        .env(PRERENDER_ENV, "list")
+        .output()?;
+    if !output.status.success() {
+        return Err(Error::BuildFailed(format!(
+            "Listing prerender routes failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    serde_json::from_slice(&output.stdout).map_err(|e| {
+        Error::BuildFailed(format!(
+            "The route list must be a JSON array of strings: {e}"
+        ))
+    })
+}
+
+/// Map a route to a directory relative to `out_dir`, refusing anything that escapes it
+fn route_path(route: &str) -> Result<PathBuf> {
+    let route = route.split(['?', '#']).next().unwrap_or_default();
+    let path = PathBuf::from(route.trim_matches('/'));
+    if path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return Err(Error::BuildFailed(format!(
+            "Invalid prerender route `{route}`."
+        )));
+    }
+    Ok(path)
+}
+
+fn insert_markup(index_html: &str, markup: &str) -> Option<String> {
+    let root = "<div id=\"main\">";
+    let start = index_html.find(root)? + root.len();
+    Some(format!(
+        "{}{}{}",
+        &index_html[..start],
+        markup,
+        &index_html[start..]
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn route_paths() {
+        assert_eq!(route_path("/").unwrap(), PathBuf::new());
+        assert_eq!(
+            route_path("/blog/post/").unwrap(),
+            PathBuf::from("blog").join("post")
+        );
+        assert_eq!(route_path("/about?tab=1").unwrap(), PathBuf::from("about"));
+        assert!(route_path("/../secret").is_err());
+    }
+
+    #[test]
+    fn markup_is_inserted_into_root() {
+        let page = insert_markup("<body><div id=\"main\"></div></body>", "<p>hi</p>").unwrap();
+        assert_eq!(page, "<body><div id=\"main\"><p>hi</p></div></body>");
+        assert!(insert_markup("<body></body>", "<p>hi</p>").is_none());
+    }
+}
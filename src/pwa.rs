@@ -0,0 +1,276 @@
+//! Progressive web app support: web manifest, app icons and an offline service worker
+use crate::{
+    config::{CrateConfig, PwaOfflineStrategy, WebEntryRole},
+    error::{Error, Result},
+    DioxusConfig,
+};
+use serde_json::json;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+pub const MANIFEST_FILE: &str = "manifest.webmanifest";
+pub const SERVICE_WORKER_FILE: &str = "service-worker.js";
+
+const DEFAULT_ICON_SIZES: [u32; 2] = [192, 512];
+
+/// The url prefix all build outputs are served under
+pub fn url_prefix(config: &DioxusConfig) -> String {
+    match &config.web.app.base_path {
+        Some(path) => format!("/{}/", path.trim_matches('/')),
+        None => "/".to_string(),
+    }
+}
+
+/// A user defined `service_worker` entry takes precedence over the generated one
+pub fn uses_generated_service_worker(config: &DioxusConfig) -> bool {
+    config.web.pwa.is_some()
+        && !config
+            .web
+            .entries()
+            .iter()
+            .any(|entry| entry.role == WebEntryRole::ServiceWorker)
+}
+
+/// Generate the manifest, icons and service worker into `out_dir`
+///
+/// This must run after everything else has been written to `out_dir`, since the service worker
+/// precaches the files written there since `build_start`. Older files are left over from
+/// previous builds and aren't part of the app anymore.
+pub fn generate(config: &CrateConfig, build_start: SystemTime) -> Result<()> {
+    let Some(pwa) = &config.dioxus_config.web.pwa else {
+        return Ok(());
+    };
+    let dioxus_config = &config.dioxus_config;
+    let prefix = url_prefix(dioxus_config);
+
+    log::info!("📱 Generating progressive web app files...");
+
+    // [1] resize the app icons
+    let mut icons = vec![];
+    if let Some(icon) = &pwa.icon {
+        let source = config.asset_dir.join(icon);
+        let image = image::open(&source).map_err(|e| {
+            Error::BuildFailed(format!("Failed to open PWA icon {}: {e}", source.display()))
+        })?;
+
+        let icon_dir = config.out_dir.join("icons");
+        std::fs::create_dir_all(&icon_dir)?;
+        for size in pwa
+            .icon_sizes
+            .clone()
+            .unwrap_or_else(|| DEFAULT_ICON_SIZES.to_vec())
+        {
+            let file_name = format!("icon-{size}x{size}.png");
+            image
+                .resize_exact(size, size, image::imageops::FilterType::Lanczos3)
+                .save(icon_dir.join(&file_name))
+                .map_err(|e| Error::BuildFailed(format!("Failed to resize PWA icon: {e}")))?;
+            icons.push(json!({
+                "src": format!("{prefix}icons/{file_name}"),
+                "sizes": format!("{size}x{size}"),
+                "type": "image/png",
+            }));
+        }
+    }
+
+    // [2] write the web manifest
+    let name = pwa
+        .name
+        .clone()
+        .unwrap_or_else(|| dioxus_config.application.name.clone());
+    let manifest = json!({
+        "name": name,
+        "short_name": pwa.short_name.clone().unwrap_or_else(|| name.clone()),
+        "description": pwa.description.clone().unwrap_or_default(),
+        "start_url": pwa.start_url.clone().unwrap_or_else(|| prefix.clone()),
+        "scope": prefix,
+        "display": pwa.display.clone().unwrap_or_else(|| "standalone".into()),
+        "theme_color": pwa.theme_color.clone().unwrap_or_else(|| "#ffffff".into()),
+        "background_color": pwa.background_color.clone().unwrap_or_else(|| "#ffffff".into()),
+        "icons": icons,
+    });
+    std::fs::write(
+        config.out_dir.join(MANIFEST_FILE),
+        serde_json::to_string_pretty(&manifest).unwrap(),
+    )?;
+
+    // [3] write the service worker precaching every build output
+    if !uses_generated_service_worker(dioxus_config) {
+        log::warn!(
+            "A `service_worker` web entry exists, skipping the generated PWA service worker."
+        );
+        return Ok(());
+    }
+
+    let files = precache_files(&config.out_dir, build_start);
+    let mut hasher = DefaultHasher::new();
+    let mut precache = vec![];
+    for file in &files {
+        let relative = file.strip_prefix(&config.out_dir).unwrap();
+        let url = format!("{prefix}{}", relative.to_str().unwrap().replace('\\', "/"));
+        url.hash(&mut hasher);
+        std::fs::read(file)?.hash(&mut hasher);
+        precache.push(url);
+    }
+    // the cache name changes whenever an output changes, so old caches are evicted on activate
+    let cache_name = format!(
+        "dioxus-{}-{:016x}",
+        dioxus_config.application.name,
+        hasher.finish()
+    );
+
+    let strategy = match pwa.offline_strategy {
+        PwaOfflineStrategy::CacheFirst => "cache_first",
+        PwaOfflineStrategy::NetworkFirst => "network_first",
+    };
+    let service_worker = include_str!("./assets/service-worker.js")
+        .replace("{cache_name}", &cache_name)
+        .replace("{precache}", &serde_json::to_string(&precache).unwrap())
+        .replace("{index}", &format!("{prefix}index.html"))
+        .replace("{strategy}", strategy);
+    std::fs::write(config.out_dir.join(SERVICE_WORKER_FILE), service_worker)?;
+
+    Ok(())
+}
+
+fn precache_files(out_dir: &Path, build_start: SystemTime) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = walkdir::WalkDir::new(out_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| {
+            e.metadata()
+                .and_then(|meta| meta.modified())
+                .map_or(false, |modified| modified >= build_start)
+        })
+        .map(|e| e.into_path())
+        .filter(|path| path.file_name().and_then(|n| n.to_str()) != Some(SERVICE_WORKER_FILE))
+        .collect();
+    // keep the cache name stable between identical builds
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::WebPwaConfig;
+    use std::time::Duration;
+
+    fn test_config(dir: &Path, pwa: WebPwaConfig) -> CrateConfig {
+        let mut config = CrateConfig::for_test(dir);
+        config.dioxus_config.web.pwa = Some(pwa);
+        config
+    }
+
+    /// A fresh crate dir with empty `dist` and `public` dirs
+    fn fixture(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dioxus-pwa-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("dist")).unwrap();
+        std::fs::create_dir_all(dir.join("public")).unwrap();
+        dir
+    }
+
+    fn read_service_worker(config: &CrateConfig) -> String {
+        std::fs::read_to_string(config.out_dir.join(SERVICE_WORKER_FILE)).unwrap()
+    }
+
+    fn cache_name(service_worker: &str) -> String {
+        let pattern = regex::Regex::new(r#"const CACHE_NAME = "([^"]+)";"#).unwrap();
+        pattern.captures(service_worker).unwrap()[1].to_string()
+    }
+
+    #[test]
+    fn manifest_and_icons() {
+        let dir = fixture("manifest");
+        image::RgbaImage::new(64, 64)
+            .save(dir.join("public").join("logo.png"))
+            .unwrap();
+        let config = test_config(
+            &dir,
+            WebPwaConfig {
+                name: Some("My App".into()),
+                icon: Some("logo.png".into()),
+                icon_sizes: Some(vec![48, 96]),
+                theme_color: Some("#000000".into()),
+                ..Default::default()
+            },
+        );
+        generate(&config, SystemTime::now()).unwrap();
+
+        let manifest = std::fs::read_to_string(config.out_dir.join(MANIFEST_FILE)).unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+        assert_eq!(manifest["name"], "My App");
+        assert_eq!(manifest["short_name"], "My App");
+        assert_eq!(manifest["start_url"], "/");
+        assert_eq!(manifest["display"], "standalone");
+        assert_eq!(manifest["theme_color"], "#000000");
+        assert_eq!(manifest["background_color"], "#ffffff");
+        assert_eq!(
+            manifest["icons"],
+            json!([
+                { "src": "/icons/icon-48x48.png", "sizes": "48x48", "type": "image/png" },
+                { "src": "/icons/icon-96x96.png", "sizes": "96x96", "type": "image/png" },
+            ])
+        );
+        for size in [48, 96] {
+            let icon = config.out_dir.join(format!("icons/icon-{size}x{size}.png"));
+            assert_eq!(image::image_dimensions(icon).unwrap(), (size, size));
+        }
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn only_the_current_build_is_precached() {
+        let dir = fixture("precache");
+        let config = test_config(&dir, WebPwaConfig::default());
+        std::fs::write(config.out_dir.join("stale.js"), "// left over").unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+
+        let build_start = SystemTime::now();
+        std::fs::write(config.out_dir.join("index.html"), "<html></html>").unwrap();
+        std::fs::create_dir_all(config.out_dir.join("assets")).unwrap();
+        std::fs::write(config.out_dir.join("assets/app.js"), "// app").unwrap();
+        generate(&config, build_start).unwrap();
+
+        let service_worker = read_service_worker(&config);
+        let precache = regex::Regex::new(r"const PRECACHE = (.*);").unwrap();
+        let precache: Vec<String> =
+            serde_json::from_str(&precache.captures(&service_worker).unwrap()[1]).unwrap();
+        assert_eq!(
+            precache,
+            ["/assets/app.js", "/index.html", "/manifest.webmanifest"]
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn cache_name_follows_the_outputs() {
+        let dir = fixture("cache-name");
+        let config = test_config(&dir, WebPwaConfig::default());
+        let build_start = SystemTime::now();
+        std::fs::write(config.out_dir.join("index.html"), "<html></html>").unwrap();
+
+        generate(&config, build_start).unwrap();
+        let first = cache_name(&read_service_worker(&config));
+        assert!(first.starts_with("dioxus-app-"));
+
+        // an identical build keeps the cache
+        generate(&config, build_start).unwrap();
+        assert_eq!(cache_name(&read_service_worker(&config)), first);
+
+        // a changed output evicts it
+        std::fs::write(config.out_dir.join("index.html"), "<html>v2</html>").unwrap();
+        generate(&config, build_start).unwrap();
+        assert_ne!(cache_name(&read_service_worker(&config)), first);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,127 @@
+//! Browser console output forwarded over the `/_dioxus/ws` websocket
+//!
+//! `autoreload.js` sends `console.*` calls, uncaught errors and wasm panics back to the CLI, which
+//! makes the console of a phone on the LAN readable from the terminal.
+use serde::Deserialize;
+
+lazy_static::lazy_static! {
+    // legacy `_ZN...E` and v0 `_R...` rust symbols, as kept in the wasm names section
+    static ref MANGLED_SYMBOL: regex::Regex =
+        regex::Regex::new(r"_ZN[0-9A-Za-z_$.]+E|_R[0-9A-Za-z_]+").unwrap();
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// A `console.*` call, including the ones of `console_error_panic_hook`
+    Log { level: String, message: String },
+    /// An uncaught error or unhandled promise rejection
+    Error {
+        message: String,
+        stack: Option<String>,
+    },
+}
+
+impl ClientMessage {
+    /// Print the message tagged with the device it came from
+    pub fn print(&self, device: &str) {
+        match self {
+            ClientMessage::Log { level, message } => {
+                let message = demangle(message);
+                let level = match level.as_str() {
+                    _ if message.contains("panicked at") => log::Level::Error,
+                    "error" => log::Level::Error,
+                    "warn" => log::Level::Warn,
+                    _ => log::Level::Info,
+                };
+                print(level, format!("[{device}] {message}"));
+            }
+            ClientMessage::Error { message, stack } => {
+                let stack = stack.as_deref().map(demangle).unwrap_or_default();
+                print(
+                    log::Level::Error,
+                    format!("[{device}] Uncaught {message}\n{stack}"),
+                );
+            }
+        }
+    }
+}
+
+/// The interactive UI shows browser output next to the output of desktop apps
+fn print(level: log::Level, message: String) {
+    if crate::tui::is_active() {
+        for line in message.lines() {
+            crate::tui::send(crate::tui::TuiEvent::AppLog(line.to_string()));
+        }
+    } else {
+        log::log!(level, "{message}");
+    }
+}
+
+/// A short name for the browser and system of a user agent, like `Chrome on Android`
+pub fn device_name(user_agent: &str) -> String {
+    let system = [
+        ("iPhone", "iPhone"),
+        ("iPad", "iPad"),
+        ("Android", "Android"),
+        ("Windows", "Windows"),
+        ("Mac OS X", "macOS"),
+        ("Linux", "Linux"),
+    ]
+    .into_iter()
+    .find(|(pattern, _)| user_agent.contains(pattern))
+    .map(|(_, name)| name);
+    // Chrome based browsers mention Chrome and Safari as well, so the order matters
+    let browser = [
+        ("Firefox/", "Firefox"),
+        ("Edg/", "Edge"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+    ]
+    .into_iter()
+    .find(|(pattern, _)| user_agent.contains(pattern))
+    .map(|(_, name)| name);
+
+    match (browser, system) {
+        (Some(browser), Some(system)) => format!("{browser} on {system}"),
+        (Some(name), None) | (None, Some(name)) => name.to_string(),
+        (None, None) => "browser".to_string(),
+    }
+}
+
+/// Demangle every rust symbol in `text`, dropping the hashes
+fn demangle(text: &str) -> String {
+    MANGLED_SYMBOL
+        .replace_all(text, |captures: &regex::Captures| {
+            let symbol = &captures[0];
+            match rustc_demangle::try_demangle(symbol) {
+                Ok(demangled) => format!("{demangled:#}"),
+                Err(_) => symbol.to_string(),
+            }
+        })
+        .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn demangles_stack_frames() {
+        let stack = "at app.wasm._ZN4core9panicking5panic17h0123456789abcdefE (wasm-function[12])";
+        assert_eq!(
+            demangle(stack),
+            "at app.wasm.core::panicking::panic (wasm-function[12])"
+        );
+        assert_eq!(demangle("no symbols here"), "no symbols here");
+    }
+
+    #[test]
+    fn device_names() {
+        let pixel = "Mozilla/5.0 (Linux; Android 13; Pixel 7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/116.0.0.0 Mobile Safari/537.36";
+        assert_eq!(device_name(pixel), "Chrome on Android");
+        let iphone = "Mozilla/5.0 (iPhone; CPU iPhone OS 16_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.6 Mobile/15E148 Safari/604.1";
+        assert_eq!(device_name(iphone), "Safari on iPhone");
+        assert_eq!(device_name(""), "browser");
+    }
+}
@@ -0,0 +1,291 @@
+//! Decide which file system events should trigger a rebuild, and what kind of rebuild
+use crate::CrateConfig;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use notify::{event::ModifyKind, EventKind};
+use std::path::{Path, PathBuf};
+
+/// Files written by editors and operating systems that are never worth a rebuild
+const DEFAULT_IGNORE: &[&str] = &[
+    "**/*.swp",
+    "**/*.swx",
+    "**/*~",
+    "**/.#*",
+    "**/#*#",
+    "**/4913",
+    "**/.DS_Store",
+    "**/*.tmp",
+];
+
+/// What a changed file means for the running app
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// A stylesheet or sass source in the asset dir, or the tailwind input, which can be swapped
+    /// without reloading the page
+    Style,
+    /// Any other file from the asset dir, which only needs the asset pipeline
+    Asset,
+    /// Everything else needs a cargo rebuild
+    Code,
+}
+
+pub struct WatchFilter {
+    crate_dir: PathBuf,
+    asset_dir: PathBuf,
+    tailwind_input: Option<PathBuf>,
+    ignored_dirs: Vec<PathBuf>,
+    gitignores: Vec<Gitignore>,
+    ignore: GlobSet,
+    include: Option<GlobSet>,
+}
+
+impl WatchFilter {
+    pub fn new(config: &CrateConfig) -> Self {
+        let watcher = &config.dioxus_config.web.watcher;
+
+        let mut ignore = watcher.ignore.clone().unwrap_or_default();
+        ignore.extend(DEFAULT_IGNORE.iter().map(|s| s.to_string()));
+
+        // anchored patterns are relative to the directory of their `.gitignore`, so the files of
+        // the crate and of its workspace are matched separately
+        let mut gitignores = vec![];
+        if watcher.gitignore.unwrap_or(true) {
+            gitignores.extend(load_gitignore(&config.crate_dir));
+            if config.workspace_dir != config.crate_dir {
+                gitignores.extend(load_gitignore(&config.workspace_dir));
+            }
+        }
+
+        Self {
+            crate_dir: config.crate_dir.clone(),
+            asset_dir: config.asset_dir.clone(),
+            tailwind_input: crate::builder::tailwind_input(config),
+            // our own output would otherwise trigger an endless rebuild loop
+            ignored_dirs: vec![config.out_dir.clone(), config.target_dir.clone()],
+            gitignores,
+            ignore: build_glob_set(&ignore),
+            include: watcher.include.as_ref().map(|globs| build_glob_set(globs)),
+        }
+    }
+
+    /// Access and metadata events don't change the contents of a file
+    pub fn is_relevant_event(kind: &EventKind) -> bool {
+        !matches!(
+            kind,
+            EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_))
+        )
+    }
+
+    /// Drop the paths of `event` that shouldn't trigger a rebuild
+    pub fn filter_event(&self, event: notify::Event) -> Vec<PathBuf> {
+        if !Self::is_relevant_event(&event.kind) {
+            return vec![];
+        }
+        event
+            .paths
+            .into_iter()
+            .filter(|path| self.is_watched(path))
+            .collect()
+    }
+
+    pub fn is_watched(&self, path: &Path) -> bool {
+        if self.ignored_dirs.iter().any(|dir| path.starts_with(dir)) {
+            return false;
+        }
+
+        let relative = path.strip_prefix(&self.crate_dir).unwrap_or(path);
+        if self.ignore.is_match(relative) {
+            return false;
+        }
+        if let Some(include) = &self.include {
+            if !include.is_match(relative) {
+                return false;
+            }
+        }
+
+        for gitignore in &self.gitignores {
+            // the matcher panics for paths outside of its root
+            if path.starts_with(gitignore.path())
+                && gitignore
+                    .matched_path_or_any_parents(path, path.is_dir())
+                    .is_ignore()
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub fn categorize(&self, path: &Path) -> ChangeKind {
+        if self.tailwind_input.as_deref() == Some(path) {
+            return ChangeKind::Style;
+        }
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        if !path.starts_with(&self.asset_dir) {
+            return ChangeKind::Code;
+        }
+        match extension {
+            "css" | "scss" | "sass" => ChangeKind::Style,
+            _ => ChangeKind::Asset,
+        }
+    }
+
+    /// The kind of rebuild needed for a set of changes: the most expensive kind of any path
+    pub fn categorize_all(&self, paths: &[PathBuf]) -> ChangeKind {
+        let kinds: Vec<ChangeKind> = paths.iter().map(|path| self.categorize(path)).collect();
+        if kinds.contains(&ChangeKind::Code) || kinds.is_empty() {
+            ChangeKind::Code
+        } else if kinds.contains(&ChangeKind::Asset) {
+            ChangeKind::Asset
+        } else {
+            ChangeKind::Style
+        }
+    }
+}
+
+fn load_gitignore(dir: &Path) -> Option<Gitignore> {
+    let file = dir.join(".gitignore");
+    if !file.is_file() {
+        return None;
+    }
+    let mut builder = GitignoreBuilder::new(dir);
+    if let Some(err) = builder.add(file) {
+        log::warn!("Failed to read .gitignore: {}", err);
+    }
+    builder.build().ok()
+}
+
+fn build_glob_set(globs: &[String]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        match Glob::new(glob) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(err) => log::warn!("Invalid watcher glob `{}`: {}", glob, err),
+        }
+    }
+    builder.build().unwrap_or_else(|_| GlobSet::empty())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind, DataChange, MetadataKind};
+
+    fn test_filter(include: Option<&[&str]>) -> WatchFilter {
+        let globs = |globs: &[&str]| {
+            build_glob_set(&globs.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        };
+        WatchFilter {
+            crate_dir: PathBuf::from("/app"),
+            asset_dir: PathBuf::from("/app/public"),
+            tailwind_input: Some(PathBuf::from("/app/input.css")),
+            ignored_dirs: vec![PathBuf::from("/app/dist"), PathBuf::from("/app/target")],
+            gitignores: vec![],
+            ignore: globs(DEFAULT_IGNORE),
+            include: include.map(globs),
+        }
+    }
+
+    #[test]
+    fn ignored_paths() {
+        let filter = test_filter(None);
+        assert!(filter.is_watched(Path::new("/app/src/main.rs")));
+        assert!(!filter.is_watched(Path::new("/app/dist/index.html")));
+        assert!(!filter.is_watched(Path::new("/app/target/debug/app")));
+        assert!(!filter.is_watched(Path::new("/app/src/.main.rs.swp")));
+        assert!(!filter.is_watched(Path::new("/app/src/main.rs~")));
+
+        let filter = test_filter(Some(&["src/**"]));
+        assert!(filter.is_watched(Path::new("/app/src/main.rs")));
+        assert!(!filter.is_watched(Path::new("/app/Cargo.toml")));
+    }
+
+    #[test]
+    fn irrelevant_events() {
+        let filter = test_filter(None);
+        let event = |kind| notify::Event::new(kind).add_path(PathBuf::from("/app/src/main.rs"));
+
+        let changed = event(EventKind::Modify(ModifyKind::Data(DataChange::Any)));
+        assert_eq!(filter.filter_event(changed).len(), 1);
+        let created = event(EventKind::Create(CreateKind::File));
+        assert_eq!(filter.filter_event(created).len(), 1);
+        let accessed = event(EventKind::Access(AccessKind::Any));
+        assert!(filter.filter_event(accessed).is_empty());
+        let touched = event(EventKind::Modify(ModifyKind::Metadata(MetadataKind::Any)));
+        assert!(filter.filter_event(touched).is_empty());
+    }
+
+    #[test]
+    fn change_kinds() {
+        let filter = test_filter(None);
+        let paths = |paths: &[&str]| paths.iter().map(PathBuf::from).collect::<Vec<_>>();
+
+        assert_eq!(
+            filter.categorize(Path::new("/app/input.css")),
+            ChangeKind::Style
+        );
+        assert_eq!(
+            filter.categorize(Path::new("/app/public/main.scss")),
+            ChangeKind::Style
+        );
+        assert_eq!(
+            filter.categorize(Path::new("/app/public/logo.png")),
+            ChangeKind::Asset
+        );
+        assert_eq!(
+            filter.categorize(Path::new("/app/src/main.rs")),
+            ChangeKind::Code
+        );
+        // stylesheets outside of the asset dir are included by the code
+        assert_eq!(
+            filter.categorize(Path::new("/app/src/style.css")),
+            ChangeKind::Code
+        );
+
+        let kind = |list: &[&str]| filter.categorize_all(&paths(list));
+        assert_eq!(
+            kind(&["/app/public/a.css", "/app/public/b.sass"]),
+            ChangeKind::Style
+        );
+        assert_eq!(
+            kind(&["/app/public/a.css", "/app/public/logo.png"]),
+            ChangeKind::Asset
+        );
+        assert_eq!(
+            kind(&["/app/public/logo.png", "/app/src/main.rs"]),
+            ChangeKind::Code
+        );
+        assert_eq!(kind(&[]), ChangeKind::Code);
+    }
+
+    #[test]
+    fn workspace_member_gitignores() {
+        let gitignore = |root: &str, lines: &[&str]| {
+            let mut builder = GitignoreBuilder::new(root);
+            for line in lines {
+                builder.add_line(None, line).unwrap();
+            }
+            builder.build().unwrap()
+        };
+        let mut filter = test_filter(None);
+        filter.crate_dir = PathBuf::from("/ws/crates/app");
+        filter.asset_dir = PathBuf::from("/ws/crates/app/public");
+        filter.gitignores = vec![
+            gitignore("/ws/crates/app", &["/out"]),
+            gitignore("/ws", &["/build", "/crates/app/gen", "*.log"]),
+        ];
+
+        assert!(!filter.is_watched(Path::new("/ws/crates/app/out/a.rs")));
+        assert!(!filter.is_watched(Path::new("/ws/crates/app/gen/a.rs")));
+        assert!(!filter.is_watched(Path::new("/ws/crates/app/src/debug.log")));
+        // anchored to the workspace root, not to the member
+        assert!(filter.is_watched(Path::new("/ws/crates/app/build/a.rs")));
+        assert!(filter.is_watched(Path::new("/ws/crates/app/src/main.rs")));
+    }
+}
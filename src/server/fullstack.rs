@@ -0,0 +1,256 @@
+//! Dev server for the `fullstack` platform
+//!
+//! The wasm client and the server binary are rebuilt together on every code change, asset and
+//! style changes only refresh the client. The app server runs as a child process and the dev
+//! server forwards every request it doesn't handle itself to it, so the browser only ever talks
+//! to the dev server.
+use super::{
+    filter::{ChangeKind, WatchFilter},
+    network, plugin, print_console_info, proxy, setup_file_watcher, ws_handler, BuildManager,
+    PrettierOptions, ReloadSender, WsReloadState,
+};
+use crate::{
+    builder::{self, BuildResult},
+    plugin::{runtime, PluginManager},
+    serve::Serve,
+    CrateConfig, Result,
+};
+use axum::{extract::Extension, routing::get, Router};
+use std::{
+    path::PathBuf,
+    process::{Child, Command},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// The environment variable holding the path of the built client assets
+pub const DIST_DIR_ENV: &str = "DIOXUS_DIST_DIR";
+/// The environment variable holding the address the server should listen on
+pub const SERVER_ADDR_ENV: &str = "DIOXUS_SERVER_ADDR";
+
+/// The running app server
+struct ServerProcess {
+    config: CrateConfig,
+    addr: String,
+    child: Option<Child>,
+}
+
+impl ServerProcess {
+    /// (Re)start the server from `binary`, see [`wait_until_listening`]
+    fn start(&mut self, binary: PathBuf) -> Result<()> {
+        self.stop();
+
+        log::info!("🛰  Starting server at {}", self.addr);
+        let child = Command::new(&binary)
+            .current_dir(&self.config.crate_dir)
+            .env(DIST_DIR_ENV, &self.config.out_dir)
+            .env(SERVER_ADDR_ENV, &self.addr)
+            .stdout(std::process::Stdio::inherit())
+            .stderr(std::process::Stdio::inherit())
+            .spawn()?;
+        self.child = Some(child);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Drop for ServerProcess {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Wait for the server at `addr` to accept connections
+///
+/// The browser shouldn't be told to reload before the server can answer it.
+async fn wait_until_listening(addr: &str) {
+    for _ in 0..50 {
+        let connect = tokio::net::TcpStream::connect(addr);
+        if let Ok(Ok(_)) = tokio::time::timeout(Duration::from_millis(100), connect).await {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    log::warn!("Server didn't start listening on {addr} in time");
+}
+
+/// Rebuild the client and the server after a code change, and restart the server
+fn rebuild_all(
+    config: &CrateConfig,
+    server: &Mutex<ServerProcess>,
+    addr: &str,
+    reload_tx: &ReloadSender,
+) -> Result<BuildResult> {
+    log::info!("🪁 Rebuild project");
+    let (res, server_binary) = builder::build_fullstack(config, true)?;
+    let started = server.lock().unwrap().start(server_binary);
+    match started {
+        Ok(()) => runtime::block_on(wait_until_listening(addr)),
+        Err(err) => log::error!("Failed to restart server: {}", err),
+    }
+    reload_tx.reload(config);
+    Ok(res)
+}
+
+pub async fn startup(port: u16, config: CrateConfig, start_browser: bool) -> Result<()> {
+    let fullstack = config.dioxus_config.fullstack.clone().unwrap_or_default();
+    let server_addr = fullstack.server_addr();
+    let ip = network::select_host(config.network.as_deref());
+
+    let (first_build_result, server_binary) = builder::build_fullstack(&config, false)?;
+
+    let app_server = Arc::new(Mutex::new(ServerProcess {
+        config: config.clone(),
+        addr: server_addr.clone(),
+        child: None,
+    }));
+    app_server.lock().unwrap().start(server_binary)?;
+    wait_until_listening(&server_addr).await;
+
+    // ctrl-c shutdown checker
+    let crate_config = config.clone();
+    let shutdown_server = app_server.clone();
+    let _ = ctrlc::set_handler(move || {
+        if let Ok(mut server) = shutdown_server.lock() {
+            server.stop();
+        }
+        let _ = PluginManager::on_serve_shutdown(&crate_config);
+        std::process::exit(0);
+    });
+
+    log::info!("🚀 Starting development server...");
+
+    let reload_tx = ReloadSender::new();
+    Serve::regen_dev_page(&config, reload_tx.build_id())?;
+    let ws_reload_state = Arc::new(WsReloadState {
+        update: reload_tx.clone(),
+    });
+
+    // file watcher: check file change
+    let watcher_config = config.clone();
+    let watcher_ip = ip.clone();
+    let watcher_server = app_server.clone();
+    let watcher_addr = server_addr.clone();
+    let filter = WatchFilter::new(&config);
+    // asset and style changes only concern the client, the server keeps running for them
+    let client_build_manager = BuildManager::new(
+        builder::fullstack_client_config(&config),
+        reload_tx.clone(),
+        None,
+    );
+    let _watcher = setup_file_watcher(&config, move |paths| {
+        let config = watcher_config.clone();
+        let result = match filter.categorize_all(&paths) {
+            ChangeKind::Code => rebuild_all(&config, &watcher_server, &watcher_addr, &reload_tx),
+            kind => client_build_manager.rebuild_changes(kind, &paths),
+        };
+        let res = result?;
+        print_console_info(
+            &watcher_ip,
+            port,
+            &config,
+            PrettierOptions {
+                changed: paths.clone(),
+                warnings: res.warnings,
+                elapsed_time: res.elapsed_time,
+            },
+        );
+        let _ = PluginManager::on_serve_rebuild(chrono::Local::now().timestamp(), paths);
+        Ok(())
+    })?;
+
+    print_console_info(
+        &ip,
+        port,
+        &config,
+        PrettierOptions {
+            changed: vec![],
+            warnings: first_build_result.warnings,
+            elapsed_time: first_build_result.elapsed_time,
+        },
+    );
+
+    PluginManager::on_serve_start(&config)?;
+
+    let mut router = Router::new().route("/_dioxus/ws", get(ws_handler));
+    for proxy_config in config.dioxus_config.web.proxy.clone().unwrap_or_default() {
+        router = proxy::add_proxy(router, &proxy_config)?;
+    }
+    router = plugin::add_plugin_routes(router, &config);
+    // everything else is answered by the app server
+    router = proxy::add_fallback_proxy(router, &format!("http://{}", server_addr))?;
+    let router = plugin::add_plugin_middleware(router).layer(Extension(ws_reload_state));
+
+    let addr = format!("0.0.0.0:{}", port).parse().unwrap();
+    let server = axum::Server::bind(&addr).serve(router.into_make_service());
+
+    if start_browser {
+        let _ = open::that(format!("http://{}", addr));
+    }
+
+    server.await?;
+
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn is_running(pid: u32) -> bool {
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .status()
+            .unwrap()
+            .success()
+    }
+
+    #[test]
+    fn server_restarts_and_stops() {
+        let dir = std::env::temp_dir().join(format!("dioxus-server-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let binary = dir.join("server");
+        let script = format!("#!/bin/sh\necho \"${SERVER_ADDR_ENV}\" > addr\nexec sleep 30\n");
+        std::fs::write(&binary, script).unwrap();
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut server = ServerProcess {
+            config: CrateConfig::for_test(&dir),
+            addr: "127.0.0.1:8081".into(),
+            child: None,
+        };
+        server.start(binary.clone()).unwrap();
+        let first = server.child.as_ref().unwrap().id();
+        assert!(is_running(first));
+
+        // the server runs in the crate dir and is told where to listen
+        let addr = dir.join("addr");
+        for _ in 0..50 {
+            if std::fs::read_to_string(&addr).map_or(false, |addr| !addr.is_empty()) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(std::fs::read_to_string(&addr).unwrap(), "127.0.0.1:8081\n");
+
+        // a restart replaces the old process
+        server.start(binary).unwrap();
+        let second = server.child.as_ref().unwrap().id();
+        assert!(!is_running(first));
+        assert!(is_running(second));
+
+        server.stop();
+        assert!(server.child.is_none());
+        assert!(!is_running(second));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,168 @@
+//! The rsx of every local crate the app is built from
+//!
+//! Besides the crate itself, workspace members and `path` dependencies are indexed, so editing an
+//! `rsx!` call in a component library of the workspace is hot reloaded too.
+use crate::CrateConfig;
+use dioxus_core::Template;
+use dioxus_html::HtmlCtx;
+use dioxus_rsx::hot_reload::{FileMap, FileMapBuildResult, UpdateResult};
+use std::path::{Path, PathBuf};
+
+pub struct RsxFiles {
+    /// The directory of the app crate
+    crate_dir: PathBuf,
+    /// The directory of every indexed crate with the rsx of its files, the app crate first
+    crates: Vec<(PathBuf, FileMap<HtmlCtx>)>,
+}
+
+impl RsxFiles {
+    pub fn create(config: &CrateConfig) -> Self {
+        Self::index(&config.crate_dir)
+    }
+
+    /// Index the app crate in `crate_dir` and every local crate it is built from
+    fn index(crate_dir: &Path) -> Self {
+        let mut dirs = vec![crate_dir.to_path_buf()];
+        match crate::cargo::local_crate_dirs(crate_dir) {
+            Ok(local_crates) => dirs.extend(local_crates),
+            Err(err) => log::warn!("Hot reloading only the current crate: {}", err),
+        }
+        Self::from_dirs(crate_dir, dirs)
+    }
+
+    fn from_dirs(crate_dir: &Path, dirs: Vec<PathBuf>) -> Self {
+        let crates = dirs
+            .into_iter()
+            .filter_map(|dir| match FileMap::<HtmlCtx>::create(dir.clone()) {
+                Ok(FileMapBuildResult { map, errors }) => {
+                    for err in errors {
+                        log::error!("{}", err);
+                    }
+                    Some((dir, map))
+                }
+                Err(err) => {
+                    log::error!(
+                        "Failed to index {} for hot reloading: {}",
+                        dir.display(),
+                        err
+                    );
+                    None
+                }
+            })
+            .collect();
+        Self {
+            crate_dir: crate_dir.to_path_buf(),
+            crates,
+        }
+    }
+
+    /// Index the crates again, dropping every template of the previous build
+    ///
+    /// The local crates are looked up again, the rebuild may come from a new workspace member.
+    pub fn reset(&mut self) {
+        *self = Self::index(&self.crate_dir);
+    }
+
+    /// The directories of the crates other than the app crate
+    pub fn dependency_dirs(&self) -> impl Iterator<Item = &Path> {
+        self.crates.iter().skip(1).map(|(dir, _)| dir.as_path())
+    }
+
+    /// Diff the rsx of the changed file against the last version
+    ///
+    /// Files outside of every indexed crate always need a rebuild.
+    pub fn update_rsx(&mut self, path: &Path) -> std::io::Result<UpdateResult> {
+        // a path dependency may live inside the app crate, so the most specific crate wins
+        let owner = self
+            .crates
+            .iter_mut()
+            .filter(|(dir, _)| path.starts_with(dir))
+            .max_by_key(|(dir, _)| dir.components().count());
+        match owner {
+            Some((dir, map)) => map.update_rsx(path, dir),
+            None => Ok(UpdateResult::NeedsRebuild),
+        }
+    }
+
+    /// Every template that changed since the last build
+    pub fn templates(&self) -> Vec<Template<'static>> {
+        self.crates
+            .iter()
+            .flat_map(|(_, map)| map.map.values())
+            .filter_map(|(_, template_slot)| *template_slot)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A fresh directory for the crates of a test
+    fn fixture(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dioxus-rsx-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_crate(dir: &Path, name: &str, lib: &str) {
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        let manifest = format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n");
+        std::fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+        std::fs::write(dir.join("src").join("lib.rs"), lib).unwrap();
+    }
+
+    fn component(text: &str) -> String {
+        format!(
+            "pub fn app(cx: Scope) -> Element {{ cx.render(rsx! {{ div {{ \"{text}\" }} }}) }}\n"
+        )
+    }
+
+    #[test]
+    fn nested_crates_get_their_own_changes() {
+        let app = fixture("nested");
+        let ui = app.join("crates").join("ui");
+        write_crate(&app, "app", "");
+        write_crate(&ui, "ui", &component("old"));
+        let mut files = RsxFiles::from_dirs(&app, vec![app.clone(), ui.clone()]);
+
+        let lib = ui.join("src").join("lib.rs");
+        std::fs::write(&lib, component("new")).unwrap();
+        let UpdateResult::UpdatedRsx(templates) = files.update_rsx(&lib).unwrap() else {
+            panic!("the rsx change needs a rebuild");
+        };
+        // template locations are relative to the crate that owns the file
+        assert_eq!(templates.len(), 1);
+        assert!(templates[0].name.starts_with("src/lib.rs:"));
+
+        let _ = std::fs::remove_dir_all(app);
+    }
+
+    #[test]
+    fn reset_indexes_new_members() {
+        let workspace = fixture("members");
+        let app = workspace.join("app");
+        let ui = workspace.join("ui");
+        write_crate(&app, "app", "");
+        std::fs::write(
+            workspace.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"app\"]\n",
+        )
+        .unwrap();
+        let mut files = RsxFiles::index(&app);
+        assert_eq!(files.dependency_dirs().count(), 0);
+
+        write_crate(&ui, "ui", &component("hello"));
+        std::fs::write(
+            workspace.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"app\", \"ui\"]\n",
+        )
+        .unwrap();
+        files.reset();
+        let dirs: Vec<&Path> = files.dependency_dirs().collect();
+        assert_eq!(dirs, [ui.canonicalize().unwrap()]);
+
+        let _ = std::fs::remove_dir_all(workspace);
+    }
+}
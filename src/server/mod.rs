@@ -1,7 +1,16 @@
-use crate::{builder, plugin::PluginManager, serve::Serve, BuildResult, CrateConfig, Result};
+use crate::{
+    builder,
+    plugin::PluginManager,
+    serve::Serve,
+    tui::{self, TuiAction, TuiEvent},
+    BuildResult, CrateConfig, Result,
+};
 use axum::{
     body::{Full, HttpBody},
-    extract::{ws::Message, Extension, TypedHeader, WebSocketUpgrade},
+    extract::{
+        ws::{CloseFrame, Message, WebSocket},
+        Extension, Query, TypedHeader, WebSocketUpgrade,
+    },
     http::{
         header::{HeaderName, HeaderValue},
         Method, Response, StatusCode,
@@ -13,14 +22,17 @@ use axum::{
 use cargo_metadata::diagnostic::Diagnostic;
 use colored::Colorize;
 use dioxus_core::Template;
-use dioxus_html::HtmlCtx;
 use dioxus_rsx::hot_reload::*;
 use notify::{RecommendedWatcher, Watcher};
+use serde::Deserialize;
 use std::{
-    net::UdpSocket,
     path::PathBuf,
     process::Command,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::Duration,
 };
 use tokio::sync::broadcast;
 use tower::ServiceBuilder;
@@ -29,36 +41,169 @@ use tower_http::{
     cors::{Any, CorsLayer},
     ServiceBuilderExt,
 };
+mod console;
+pub mod filter;
+pub mod fullstack;
+mod hot_reload;
+mod network;
+mod plugin;
 mod proxy;
 
+use filter::{ChangeKind, WatchFilter};
+use hot_reload::RsxFiles;
+
+/// The version of the `/_dioxus/ws` protocol, sent to the browser when it connects
+const WS_PROTOCOL_VERSION: u32 = 2;
+/// How often an idle connection is pinged, so the browser can notice a dead server
+const WS_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Messages sent to the browser over the `/_dioxus/ws` websocket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReloadMessage {
+    /// Reload the whole page
+    Reload,
+    /// Swap the stylesheets with these urls, relative to the output dir, without reloading
+    UpdateCss(Vec<String>),
+    /// Refresh the images and links pointing to these urls, relative to the output dir
+    UpdateAssets(Vec<String>),
+}
+
+/// Broadcasts reload messages to every connected browser and numbers the builds
+///
+/// Every [`ReloadSender::reload`] starts a new build id, which is written into the dev page. A
+/// browser compares the current id with the one of its page, and only reloads if they differ.
+#[derive(Clone)]
+pub struct ReloadSender {
+    tx: broadcast::Sender<ReloadMessage>,
+    build_id: Arc<AtomicU64>,
+}
+
+impl ReloadSender {
+    pub fn new() -> Self {
+        // start from the clock so the ids of a restarted server never match an old page
+        let first_id = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_millis() as u64)
+            .unwrap_or_default();
+        Self {
+            tx: broadcast::channel(100).0,
+            build_id: Arc::new(AtomicU64::new(first_id)),
+        }
+    }
+
+    pub fn build_id(&self) -> u64 {
+        self.build_id.load(Ordering::SeqCst)
+    }
+
+    pub fn send(&self, message: ReloadMessage) {
+        // nobody may be connected yet
+        let _ = self.tx.send(message);
+    }
+
+    /// Start a new build and reload every page
+    ///
+    /// The dev page is updated first, so the reloaded pages carry the new id already.
+    pub fn reload(&self, config: &CrateConfig) {
+        let build_id = self.build_id.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Err(err) = Serve::update_dev_page(config, build_id) {
+            log::error!("Failed to update the dev page: {}", err);
+        }
+        self.send(ReloadMessage::Reload);
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<ReloadMessage> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for ReloadSender {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct BuildManager {
     config: CrateConfig,
-    reload_tx: broadcast::Sender<()>,
+    reload_tx: ReloadSender,
+    /// The rsx of the last successful build, if hot reloading is enabled
+    file_map: Option<Arc<Mutex<RsxFiles>>>,
+    /// Held during a rebuild, so the watcher and the interactive UI never run two cargo builds
+    /// into the same output dir
+    building: Mutex<()>,
 }
 
 impl BuildManager {
+    fn new(
+        config: CrateConfig,
+        reload_tx: ReloadSender,
+        file_map: Option<Arc<Mutex<RsxFiles>>>,
+    ) -> Self {
+        Self {
+            config,
+            reload_tx,
+            file_map,
+            building: Mutex::new(()),
+        }
+    }
+
+    /// Wait for the running rebuild, a panic during it doesn't stop later ones
+    fn lock_build(&self) -> MutexGuard<()> {
+        self.building.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
     fn rebuild(&self) -> Result<BuildResult> {
+        let _building = self.lock_build();
+        self.build_code()
+    }
+
+    fn build_code(&self) -> Result<BuildResult> {
         log::info!("🪁 Rebuild project");
-        let result = builder::build(&self.config, true)?;
+        tui::send(TuiEvent::BuildStarted);
+        let result = builder::build(&self.config, true).map_err(|err| {
+            tui::send(TuiEvent::BuildFailed);
+            err
+        })?;
+        // the templates of the old binary don't apply to the new one
+        if let Some(file_map) = &self.file_map {
+            file_map.lock().unwrap().reset();
+        }
         // change the websocket reload state to true;
         // the page will auto-reload.
-        if self
-            .config
-            .dioxus_config
-            .web
-            .watcher
-            .reload_html
-            .unwrap_or(false)
-        {
-            let _ = Serve::regen_dev_page(&self.config);
-        }
-        let _ = self.reload_tx.send(());
+        self.reload_tx.reload(&self.config);
         Ok(result)
     }
+
+    /// Rebuild what `kind` of change to `paths` requires, skipping cargo if no code changed
+    fn rebuild_changes(&self, kind: ChangeKind, paths: &[PathBuf]) -> Result<BuildResult> {
+        let _building = self.lock_build();
+        let t_start = std::time::Instant::now();
+        match kind {
+            ChangeKind::Code => return self.build_code(),
+            ChangeKind::Asset => {
+                log::info!("🎨 Refresh assets");
+                let hrefs = builder::copy_changed_assets(&self.config, paths)?;
+                if !hrefs.is_empty() {
+                    self.reload_tx.send(ReloadMessage::UpdateAssets(hrefs));
+                }
+            }
+            ChangeKind::Style => {
+                log::info!("🎨 Rebuild styles");
+                let hrefs = builder::rebuild_styles(&self.config, paths)?;
+                if !hrefs.is_empty() {
+                    self.reload_tx.send(ReloadMessage::UpdateCss(hrefs));
+                }
+            }
+        }
+
+        Ok(BuildResult {
+            warnings: vec![],
+            elapsed_time: t_start.elapsed().as_millis(),
+        })
+    }
 }
 
 struct WsReloadState {
-    update: broadcast::Sender<()>,
+    update: ReloadSender,
 }
 
 pub async fn startup(port: u16, config: CrateConfig, start_browser: bool) -> Result<()> {
@@ -69,9 +214,10 @@ pub async fn startup(port: u16, config: CrateConfig, start_browser: bool) -> Res
         std::process::exit(0);
     });
 
-    let ip = get_ip().unwrap_or(String::from("0.0.0.0"));
+    let ip = network::select_host(config.network.as_deref());
 
-    if config.hot_reload {
+    // the interactive UI can switch hot reloading on and off, so it always needs its state
+    if config.hot_reload || config.interactive {
         startup_hot_reload(ip, port, config, start_browser).await?
     } else {
         startup_default(ip, port, config, start_browser).await?
@@ -82,31 +228,54 @@ pub async fn startup(port: u16, config: CrateConfig, start_browser: bool) -> Res
 pub struct HotReloadState {
     pub messages: broadcast::Sender<Template<'static>>,
     pub build_manager: Arc<BuildManager>,
-    pub file_map: Arc<Mutex<FileMap<HtmlCtx>>>,
+    pub file_map: Arc<Mutex<RsxFiles>>,
     pub watcher_config: CrateConfig,
+    /// Numbers the builds, templates are only sent to clients running the current one
+    pub generation: ReloadSender,
+}
+
+#[derive(Deserialize)]
+pub struct HotReloadQuery {
+    /// The build of the page connecting, added to the url by `autoreload.js`
+    build_id: Option<u64>,
+}
+
+/// The close code telling a hot reload client its build is outdated and the page has to reload
+const OUTDATED_BUILD_CLOSE_CODE: u16 = 4000;
+
+async fn close_outdated(socket: &mut WebSocket) {
+    log::info!("🔥 Hot Reload client runs an outdated build, asking it to reload");
+    let close = CloseFrame {
+        code: OUTDATED_BUILD_CLOSE_CODE,
+        reason: "outdated build, reload the page".into(),
+    };
+    let _ = socket.send(Message::Close(Some(close))).await;
 }
 
 pub async fn hot_reload_handler(
     ws: WebSocketUpgrade,
     _: Option<TypedHeader<headers::UserAgent>>,
+    Query(query): Query<HotReloadQuery>,
     Extension(state): Extension<Arc<HotReloadState>>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(|mut socket| async move {
+    ws.on_upgrade(move |mut socket| async move {
         log::info!("🔥 Hot Reload WebSocket connected");
+        // clients that don't send their build are assumed to run the current one
+        let build_id = query
+            .build_id
+            .unwrap_or_else(|| state.generation.build_id());
+        // subscribe before checking, so a rebuild right after the check isn't missed
+        let mut rebuilds = state.generation.subscribe();
+        let mut rx = state.messages.subscribe();
+        if state.generation.build_id() != build_id {
+            close_outdated(&mut socket).await;
+            return;
+        }
         {
             // update any rsx calls that changed before the websocket connected.
             {
                 log::info!("🔮 Finding updates since last compile...");
-                let templates: Vec<_> = {
-                    state
-                        .file_map
-                        .lock()
-                        .unwrap()
-                        .map
-                        .values()
-                        .filter_map(|(_, template_slot)| *template_slot)
-                        .collect()
-                };
+                let templates = state.file_map.lock().unwrap().templates();
                 for template in templates {
                     if socket
                         .send(Message::Text(serde_json::to_string(&template).unwrap()))
@@ -120,17 +289,37 @@ pub async fn hot_reload_handler(
             log::info!("finished");
         }
 
-        let mut rx = state.messages.subscribe();
         loop {
-            if let Ok(rsx) = rx.recv().await {
-                if socket
-                    .send(Message::Text(serde_json::to_string(&rsx).unwrap()))
-                    .await
-                    .is_err()
-                {
-                    break;
-                };
+            let rsx = tokio::select! {
+                rsx = rx.recv() => match rsx {
+                    Ok(rsx) => rsx,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                rebuild = rebuilds.recv() => match rebuild {
+                    Ok(ReloadMessage::Reload) | Err(broadcast::error::RecvError::Lagged(_)) => {
+                        if state.generation.build_id() != build_id {
+                            close_outdated(&mut socket).await;
+                            break;
+                        }
+                        continue;
+                    }
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            };
+            // the rebuild may not have been received yet, templates of an old build are useless
+            if state.generation.build_id() != build_id {
+                close_outdated(&mut socket).await;
+                break;
             }
+            if socket
+                .send(Message::Text(serde_json::to_string(&rsx).unwrap()))
+                .await
+                .is_err()
+            {
+                break;
+            };
         }
     })
 }
@@ -149,54 +338,110 @@ pub async fn startup_hot_reload(
     PluginManager::on_serve_start(&config)?;
 
     let dist_path = config.out_dir.clone();
-    let (reload_tx, _) = broadcast::channel(100);
-    let FileMapBuildResult { map, errors } =
-        FileMap::<HtmlCtx>::create(config.crate_dir.clone()).unwrap();
-    for err in errors {
-        log::error!("{}", err);
-    }
-    let file_map = Arc::new(Mutex::new(map));
-    let build_manager = Arc::new(BuildManager {
-        config: config.clone(),
-        reload_tx: reload_tx.clone(),
-    });
+    let reload_tx = ReloadSender::new();
+    Serve::regen_dev_page(&config, reload_tx.build_id())?;
+    let rsx_files = RsxFiles::create(&config);
+    let dependency_dirs: Vec<PathBuf> = rsx_files.dependency_dirs().map(PathBuf::from).collect();
+    let file_map = Arc::new(Mutex::new(rsx_files));
+    let build_manager = Arc::new(BuildManager::new(
+        config.clone(),
+        reload_tx.clone(),
+        Some(file_map.clone()),
+    ));
     let hot_reload_tx = broadcast::channel(100).0;
     let hot_reload_state = Arc::new(HotReloadState {
         messages: hot_reload_tx.clone(),
         build_manager: build_manager.clone(),
         file_map: file_map.clone(),
         watcher_config: config.clone(),
+        generation: reload_tx.clone(),
     });
 
-    let crate_dir = config.crate_dir.clone();
     let ws_reload_state = Arc::new(WsReloadState {
         update: reload_tx.clone(),
     });
+    let hot_reload = Arc::new(AtomicBool::new(config.hot_reload));
+    let tui_build_manager = build_manager.clone();
+    let tui_hot_reload = hot_reload.clone();
 
     // file watcher: check file change
-    let allow_watch_path = config
-        .dioxus_config
-        .web
-        .watcher
-        .watch_path
-        .clone()
-        .unwrap_or_else(|| vec![PathBuf::from("src")]);
-
     let watcher_config = config.clone();
     let watcher_ip = ip.clone();
     let mut last_update_time = chrono::Local::now().timestamp();
 
+    let filter = WatchFilter::new(&config);
+
     let mut watcher = RecommendedWatcher::new(
         move |evt: notify::Result<notify::Event>| {
             let config = watcher_config.clone();
+            let Ok(evt) = evt else {
+                return;
+            };
+            let paths = filter.filter_event(evt);
+            if paths.is_empty() {
+                return;
+            }
             // Give time for the change to take effect before reading the file
             std::thread::sleep(std::time::Duration::from_millis(100));
             if chrono::Local::now().timestamp() > last_update_time {
-                if let Ok(evt) = evt {
-                    let mut messages: Vec<Template<'static>> = Vec::new();
-                    for path in evt.paths.clone() {
-                        // if this is not a rust file, rebuild the whole project
-                        if path.extension().and_then(|p| p.to_str()) != Some("rs") {
+                let mut messages: Vec<Template<'static>> = Vec::new();
+
+                // changes outside of the rust code only need the asset pipeline
+                let kind = filter.categorize_all(&paths);
+                if kind != ChangeKind::Code || !hot_reload.load(Ordering::SeqCst) {
+                    match build_manager.rebuild_changes(kind, &paths) {
+                        Ok(res) => {
+                            print_console_info(
+                                &watcher_ip,
+                                port,
+                                &config,
+                                PrettierOptions {
+                                    changed: paths,
+                                    warnings: res.warnings,
+                                    elapsed_time: res.elapsed_time,
+                                },
+                            );
+                        }
+                        Err(err) => {
+                            log::error!("{}", err);
+                            return;
+                        }
+                    }
+                    last_update_time = chrono::Local::now().timestamp();
+                    return;
+                }
+
+                for path in paths.clone() {
+                    // if this is not a rust file, rebuild the whole project
+                    if path.extension().and_then(|p| p.to_str()) != Some("rs") {
+                        match build_manager.rebuild() {
+                            Ok(res) => {
+                                print_console_info(
+                                    &watcher_ip,
+                                    port,
+                                    &config,
+                                    PrettierOptions {
+                                        changed: paths,
+                                        warnings: res.warnings,
+                                        elapsed_time: res.elapsed_time,
+                                    },
+                                );
+                            }
+                            Err(err) => {
+                                log::error!("{}", err);
+                            }
+                        }
+                        return;
+                    }
+                    // find changes to the rsx in the file
+                    // the lock is released before a rebuild, which resets the map
+                    let update = file_map.lock().unwrap().update_rsx(&path);
+
+                    match update {
+                        Ok(UpdateResult::UpdatedRsx(msgs)) => {
+                            messages.extend(msgs);
+                        }
+                        Ok(UpdateResult::NeedsRebuild) => {
                             match build_manager.rebuild() {
                                 Ok(res) => {
                                     print_console_info(
@@ -204,7 +449,7 @@ pub async fn startup_hot_reload(
                                         port,
                                         &config,
                                         PrettierOptions {
-                                            changed: evt.paths,
+                                            changed: paths,
                                             warnings: res.warnings,
                                             elapsed_time: res.elapsed_time,
                                         },
@@ -216,41 +461,13 @@ pub async fn startup_hot_reload(
                             }
                             return;
                         }
-                        // find changes to the rsx in the file
-                        let mut map = file_map.lock().unwrap();
-
-                        match map.update_rsx(&path, &crate_dir) {
-                            Ok(UpdateResult::UpdatedRsx(msgs)) => {
-                                messages.extend(msgs);
-                            }
-                            Ok(UpdateResult::NeedsRebuild) => {
-                                match build_manager.rebuild() {
-                                    Ok(res) => {
-                                        print_console_info(
-                                            &watcher_ip,
-                                            port,
-                                            &config,
-                                            PrettierOptions {
-                                                changed: evt.paths,
-                                                warnings: res.warnings,
-                                                elapsed_time: res.elapsed_time,
-                                            },
-                                        );
-                                    }
-                                    Err(err) => {
-                                        log::error!("{}", err);
-                                    }
-                                }
-                                return;
-                            }
-                            Err(err) => {
-                                log::error!("{}", err);
-                            }
+                        Err(err) => {
+                            log::error!("{}", err);
                         }
                     }
-                    for msg in messages {
-                        let _ = hot_reload_tx.send(msg);
-                    }
+                }
+                for msg in messages {
+                    let _ = hot_reload_tx.send(msg);
                 }
                 last_update_time = chrono::Local::now().timestamp();
             }
@@ -259,15 +476,55 @@ pub async fn startup_hot_reload(
     )
     .unwrap();
 
-    for sub_path in allow_watch_path {
-        if let Err(err) = watcher.watch(
-            &config.crate_dir.join(&sub_path),
-            notify::RecursiveMode::Recursive,
-        ) {
-            log::error!("error watching {sub_path:?}: \n{}", err);
+    for path in watch_paths(&config) {
+        if let Err(err) = watcher.watch(&path, notify::RecursiveMode::Recursive) {
+            log::error!("error watching {path:?}: \n{}", err);
+        }
+    }
+    // rsx in workspace members and path dependencies is hot reloaded too
+    for dir in dependency_dirs {
+        let src = dir.join("src");
+        if let Err(err) = watcher.watch(&src, notify::RecursiveMode::Recursive) {
+            log::error!("error watching {src:?}: \n{}", err);
         }
     }
 
+    if config.interactive {
+        let actions = tui::start(tui_info(&ip, port, &config), Some(config.hot_reload))?;
+        let config = config.clone();
+        let ip = ip.clone();
+        std::thread::spawn(move || {
+            for action in actions {
+                match action {
+                    TuiAction::Rebuild => match tui_build_manager.rebuild() {
+                        Ok(res) => print_console_info(
+                            &ip,
+                            port,
+                            &config,
+                            PrettierOptions {
+                                changed: vec![],
+                                warnings: res.warnings,
+                                elapsed_time: res.elapsed_time,
+                            },
+                        ),
+                        Err(err) => log::error!("{}", err),
+                    },
+                    TuiAction::OpenBrowser => {
+                        let _ = open::that(format!("http://localhost:{port}"));
+                    }
+                    TuiAction::ToggleHotReload => {
+                        let enabled = !tui_hot_reload.fetch_xor(true, Ordering::SeqCst);
+                        tui::send(TuiEvent::HotReload(enabled));
+                    }
+                    TuiAction::Quit => {
+                        let _ = PluginManager::on_serve_shutdown(&config);
+                        std::process::exit(0);
+                    }
+                }
+            }
+        });
+    }
+
     // start serve dev-server at 0.0.0.0:8080
     print_console_info(
         &ip,
@@ -342,9 +599,10 @@ pub async fn startup_hot_reload(
         .service(ServeDir::new(config.crate_dir.join(&dist_path)));
 
     let mut router = Router::new().route("/_dioxus/ws", get(ws_handler));
-    for proxy_config in config.dioxus_config.web.proxy.unwrap_or_default() {
+    for proxy_config in config.dioxus_config.web.proxy.clone().unwrap_or_default() {
         router = proxy::add_proxy(router, &proxy_config)?;
     }
+    router = plugin::add_plugin_routes(router, &config);
     router = router.fallback(get_service(file_service).handle_error(
         |error: std::io::Error| async move {
             (
@@ -354,8 +612,8 @@ pub async fn startup_hot_reload(
         },
     ));
 
-    let router = router
-        .route("/_dioxus/hot_reload", get(hot_reload_handler))
+    let router = router.route("/_dioxus/hot_reload", get(hot_reload_handler));
+    let router = plugin::add_plugin_middleware(router)
         .layer(cors)
         .layer(Extension(ws_reload_state))
         .layer(Extension(hot_reload_state));
@@ -385,67 +643,35 @@ pub async fn startup_default(
 
     let dist_path = config.out_dir.clone();
 
-    let (reload_tx, _) = broadcast::channel(100);
+    let reload_tx = ReloadSender::new();
+    Serve::regen_dev_page(&config, reload_tx.build_id())?;
 
-    let build_manager = BuildManager {
-        config: config.clone(),
-        reload_tx: reload_tx.clone(),
-    };
+    let build_manager = BuildManager::new(config.clone(), reload_tx.clone(), None);
 
     let ws_reload_state = Arc::new(WsReloadState {
         update: reload_tx.clone(),
     });
 
-    let mut last_update_time = chrono::Local::now().timestamp();
-
     // file watcher: check file change
-    let allow_watch_path = config
-        .dioxus_config
-        .web
-        .watcher
-        .watch_path
-        .clone()
-        .unwrap_or_else(|| vec![PathBuf::from("src")]);
-
     let watcher_config = config.clone();
     let watcher_ip = ip.clone();
-    let mut watcher = notify::recommended_watcher(move |info: notify::Result<notify::Event>| {
+    let filter = WatchFilter::new(&config);
+    let _watcher = setup_file_watcher(&config, move |paths| {
         let config = watcher_config.clone();
-        if let Ok(e) = info {
-            if chrono::Local::now().timestamp() > last_update_time {
-                match build_manager.rebuild() {
-                    Ok(res) => {
-                        last_update_time = chrono::Local::now().timestamp();
-                        print_console_info(
-                            &watcher_ip,
-                            port,
-                            &config,
-                            PrettierOptions {
-                                changed: e.paths.clone(),
-                                warnings: res.warnings,
-                                elapsed_time: res.elapsed_time,
-                            },
-                        );
-                        let _ = PluginManager::on_serve_rebuild(
-                            chrono::Local::now().timestamp(),
-                            e.paths,
-                        );
-                    }
-                    Err(e) => log::error!("{}", e),
-                }
-            }
-        }
-    })
-    .unwrap();
-
-    for sub_path in allow_watch_path {
-        watcher
-            .watch(
-                &config.crate_dir.join(sub_path),
-                notify::RecursiveMode::Recursive,
-            )
-            .unwrap();
-    }
+        let res = build_manager.rebuild_changes(filter.categorize_all(&paths), &paths)?;
+        print_console_info(
+            &watcher_ip,
+            port,
+            &config,
+            PrettierOptions {
+                changed: paths.clone(),
+                warnings: res.warnings,
+                elapsed_time: res.elapsed_time,
+            },
+        );
+        let _ = PluginManager::on_serve_rebuild(chrono::Local::now().timestamp(), paths);
+        Ok(())
+    })?;
 
     // start serve dev-server at 0.0.0.0
     print_console_info(
@@ -523,18 +749,19 @@ pub async fn startup_default(
         .service(ServeDir::new(config.crate_dir.join(&dist_path)));
 
     let mut router = Router::new().route("/_dioxus/ws", get(ws_handler));
-    for proxy_config in config.dioxus_config.web.proxy.unwrap_or_default() {
+    for proxy_config in config.dioxus_config.web.proxy.clone().unwrap_or_default() {
         router = proxy::add_proxy(router, &proxy_config)?;
     }
-    router = router
-        .fallback(
-            get_service(file_service).handle_error(|error: std::io::Error| async move {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Unhandled internal error: {}", error),
-                )
-            }),
-        )
+    router = plugin::add_plugin_routes(router, &config);
+    router = router.fallback(get_service(file_service).handle_error(
+        |error: std::io::Error| async move {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Unhandled internal error: {}", error),
+            )
+        },
+    ));
+    let router = plugin::add_plugin_middleware(router)
         .layer(cors)
         .layer(Extension(ws_reload_state));
 
@@ -550,6 +777,60 @@ pub async fn startup_default(
     Ok(())
 }
 
+/// Watch the `web.watcher.watch_path` directories of the crate
+///
+/// `on_change` is called with the changed paths that pass the [`WatchFilter`], at most once per
+/// second unless it failed, its errors are logged. The watcher stops when the returned value is
+/// dropped.
+pub fn setup_file_watcher<F>(config: &CrateConfig, mut on_change: F) -> Result<RecommendedWatcher>
+where
+    F: FnMut(Vec<PathBuf>) -> Result<()> + Send + 'static,
+{
+    let filter = WatchFilter::new(config);
+    let mut last_update_time = chrono::Local::now().timestamp();
+    let mut watcher = notify::recommended_watcher(move |info: notify::Result<notify::Event>| {
+        if let Ok(e) = info {
+            let paths = filter.filter_event(e);
+            if !paths.is_empty() && chrono::Local::now().timestamp() > last_update_time {
+                // after a failed build the next change is picked up right away
+                match on_change(paths) {
+                    Ok(()) => last_update_time = chrono::Local::now().timestamp(),
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+        }
+    })
+    .map_err(|e| crate::Error::Unique(format!("Failed to start file watcher: {e}")))?;
+
+    for path in watch_paths(config) {
+        if let Err(err) = watcher.watch(&path, notify::RecursiveMode::Recursive) {
+            log::error!("error watching {path:?}: \n{}", err);
+        }
+    }
+
+    Ok(watcher)
+}
+
+/// The `web.watcher.watch_path` directories, plus the asset dir if they don't contain it
+fn watch_paths(config: &CrateConfig) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = config
+        .dioxus_config
+        .web
+        .watcher
+        .watch_path
+        .clone()
+        .unwrap_or_else(|| vec![PathBuf::from("src")])
+        .into_iter()
+        .map(|path| config.crate_dir.join(path))
+        .collect();
+
+    // assets are refreshed in the browser without a rebuild, so they are always watched
+    if config.asset_dir.is_dir() && !paths.iter().any(|path| config.asset_dir.starts_with(path)) {
+        paths.push(config.asset_dir.clone());
+    }
+    paths
+}
+
 #[derive(Debug, Default)]
 pub struct PrettierOptions {
     changed: Vec<PathBuf>,
@@ -557,7 +838,30 @@ pub struct PrettierOptions {
     elapsed_time: u128,
 }
 
+/// The header lines of the interactive UI
+fn tui_info(ip: &str, port: u16, config: &CrateConfig) -> Vec<(String, String)> {
+    let profile = match &config.custom_profile {
+        Some(profile) => profile.clone(),
+        None if config.release => "Release".to_string(),
+        None => "Debug".to_string(),
+    };
+    vec![
+        ("Local".into(), format!("http://localhost:{port}/")),
+        ("Network".into(), format!("http://{ip}:{port}/")),
+        ("Profile".into(), profile),
+    ]
+}
+
 fn print_console_info(ip: &String, port: u16, config: &CrateConfig, options: PrettierOptions) {
+    if tui::is_active() {
+        tui::send(TuiEvent::BuildFinished {
+            changed: options.changed,
+            warnings: options.warnings,
+            elapsed_time: options.elapsed_time,
+        });
+        return;
+    }
+
     if let Ok(native_clearseq) = Command::new(if cfg!(target_os = "windows") {
         "cls"
     } else {
@@ -627,10 +931,33 @@ fn print_console_info(ip: &String, port: u16, config: &CrateConfig, options: Pre
         "\t> Local : {}",
         format!("http://localhost:{}/", port).blue()
     );
-    println!(
-        "\t> Network : {}",
-        format!("http://{}:{}/", ip, port).blue()
-    );
+    let addresses = network::addresses();
+    if addresses.is_empty() {
+        println!(
+            "\t> Network : {}",
+            format!("http://{}:{}/", ip, port).blue()
+        );
+    }
+    for address in &addresses {
+        let host = network::host(&address.ip);
+        let selected = if host == *ip { " *" } else { "" };
+        println!(
+            "\t> Network [{}] : {}{}",
+            address.interface,
+            format!("http://{}:{}/", host, port).blue(),
+            selected.green()
+        );
+    }
+    // the banner is printed again after every rebuild, but the code only needs scanning once
+    static QR_CODE_PRINTED: AtomicBool = AtomicBool::new(false);
+    if config.qr_code && !addresses.is_empty() && !QR_CODE_PRINTED.swap(true, Ordering::SeqCst) {
+        if let Some(qr_code) = network::qr_code(&format!("http://{}:{}/", ip, port)) {
+            println!("");
+            for line in qr_code.lines() {
+                println!("\t  {}", line);
+            }
+        }
+    }
     println!("");
     println!("\t> Profile : {}", profile.green());
     println!("\t> Hot Reload : {}", hot_reload.cyan());
@@ -706,47 +1033,65 @@ fn print_console_info(ip: &String, port: u16, config: &CrateConfig, options: Pre
     }
 }
 
-fn get_ip() -> Option<String> {
-    let socket = match UdpSocket::bind("0.0.0.0:0") {
-        Ok(s) => s,
-        Err(_) => return None,
-    };
-
-    match socket.connect("8.8.8.8:80") {
-        Ok(()) => (),
-        Err(_) => return None,
-    };
-
-    match socket.local_addr() {
-        Ok(addr) => return Some(addr.ip().to_string()),
-        Err(_) => return None,
-    };
-}
-
 async fn ws_handler(
     ws: WebSocketUpgrade,
-    _: Option<TypedHeader<headers::UserAgent>>,
+    user_agent: Option<TypedHeader<headers::UserAgent>>,
     Extension(state): Extension<Arc<WsReloadState>>,
 ) -> impl IntoResponse {
-    ws.on_upgrade(|mut socket| async move {
+    let device = console::device_name(user_agent.as_ref().map_or("", |ua| ua.as_str()));
+    ws.on_upgrade(move |mut socket| async move {
         let mut rx = state.update.subscribe();
-        let reload_watcher = tokio::spawn(async move {
-            loop {
-                rx.recv().await.unwrap();
-                // ignore the error
-                if socket
-                    .send(Message::Text(String::from("reload")))
-                    .await
-                    .is_err()
-                {
-                    break;
-                }
-
-                // flush the errors after recompling
-                rx = rx.resubscribe();
-            }
+        let hello = serde_json::json!({
+            "type": "hello",
+            "version": WS_PROTOCOL_VERSION,
+            "build_id": state.update.build_id(),
         });
+        if socket.send(Message::Text(hello.to_string())).await.is_err() {
+            return;
+        }
 
-        reload_watcher.await.unwrap();
+        let start = tokio::time::Instant::now() + WS_HEARTBEAT_INTERVAL;
+        let mut heartbeat = tokio::time::interval_at(start, WS_HEARTBEAT_INTERVAL);
+        loop {
+            let text = tokio::select! {
+                message = rx.recv() => match message {
+                    Ok(message) => ws_message_text(&message, state.update.build_id()),
+                    // messages were dropped while this client was slow, reloading is always safe
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        ws_message_text(&ReloadMessage::Reload, state.update.build_id())
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = heartbeat.tick() => serde_json::json!({ "type": "ping" }).to_string(),
+                incoming = socket.recv() => match incoming {
+                    Some(Ok(Message::Text(text))) => {
+                        match serde_json::from_str::<console::ClientMessage>(&text) {
+                            Ok(message) => message.print(&device),
+                            Err(err) => log::debug!("Unknown message from {device}: {err}"),
+                        }
+                        continue;
+                    }
+                    Some(Ok(_)) => continue,
+                    // the browser went away
+                    _ => break,
+                },
+            };
+            if socket.send(Message::Text(text)).await.is_err() {
+                break;
+            }
+        }
     })
 }
+
+fn ws_message_text(message: &ReloadMessage, build_id: u64) -> String {
+    let message = match message {
+        ReloadMessage::Reload => serde_json::json!({ "type": "reload", "build_id": build_id }),
+        ReloadMessage::UpdateCss(hrefs) => {
+            serde_json::json!({ "type": "update_css", "hrefs": hrefs })
+        }
+        ReloadMessage::UpdateAssets(hrefs) => {
+            serde_json::json!({ "type": "update_assets", "hrefs": hrefs })
+        }
+    };
+    message.to_string()
+}
@@ -0,0 +1,158 @@
+//! The addresses other devices in the network can open the dev server at
+use qrcode::{render::unicode, QrCode};
+use std::net::{IpAddr, UdpSocket};
+
+pub struct NetworkAddress {
+    pub interface: String,
+    pub ip: IpAddr,
+}
+
+/// Every non-loopback address of this machine, IPv4 first
+pub fn addresses() -> Vec<NetworkAddress> {
+    let interfaces = if_addrs::get_if_addrs().unwrap_or_default();
+    usable_addresses(
+        interfaces
+            .into_iter()
+            .map(|interface| (interface.ip(), interface.name)),
+    )
+}
+
+fn usable_addresses(interfaces: impl IntoIterator<Item = (IpAddr, String)>) -> Vec<NetworkAddress> {
+    let mut addresses: Vec<NetworkAddress> = interfaces
+        .into_iter()
+        .filter(|(ip, _)| !ip.is_loopback())
+        .map(|(ip, interface)| NetworkAddress { interface, ip })
+        // link local IPv6 addresses need a zone id, which browsers don't accept in urls
+        .filter(
+            |address| !matches!(address.ip, IpAddr::V6(ip) if ip.segments()[0] & 0xffc0 == 0xfe80),
+        )
+        .collect();
+    addresses.sort_by_key(|address| address.ip.is_ipv6());
+    addresses
+}
+
+/// The host of the network url shown in the banner and the QR code
+///
+/// `choice` is the interface name or address passed to `--network`. Without it, the address
+/// of the default route is preferred.
+pub fn select_host(choice: Option<&str>) -> String {
+    match select_ip(&addresses(), choice, default_route_ip()) {
+        Some(ip) => host(&ip),
+        None => String::from("0.0.0.0"),
+    }
+}
+
+fn select_ip(
+    addresses: &[NetworkAddress],
+    choice: Option<&str>,
+    default_route: Option<IpAddr>,
+) -> Option<IpAddr> {
+    let chosen = choice.and_then(|choice| {
+        let found = addresses
+            .iter()
+            .find(|address| address.interface == choice || address.ip.to_string() == choice);
+        if found.is_none() {
+            log::warn!("No network interface or address matches `{choice}`.");
+        }
+        found.map(|address| address.ip)
+    });
+    chosen
+        .or_else(|| default_route.filter(|ip| addresses.iter().any(|a| a.ip == *ip)))
+        .or_else(|| addresses.first().map(|address| address.ip))
+}
+
+/// Format `ip` for the host part of a url
+pub fn host(ip: &IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{ip}]"),
+    }
+}
+
+/// Render `url` as a QR code made of unicode half blocks
+pub fn qr_code(url: &str) -> Option<String> {
+    let code = QrCode::new(url.as_bytes()).ok()?;
+    // inverted, so it scans on dark terminal backgrounds
+    let rendered = code
+        .render::<unicode::Dense1x2>()
+        .dark_color(unicode::Dense1x2::Light)
+        .light_color(unicode::Dense1x2::Dark)
+        .quiet_zone(true)
+        .build();
+    Some(rendered)
+}
+
+/// The local address of the interface that routes to the internet
+fn default_route_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("8.8.8.8:80").ok()?;
+    Some(socket.local_addr().ok()?.ip())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn interfaces() -> Vec<NetworkAddress> {
+        usable_addresses([
+            ("::1".parse().unwrap(), "lo".to_string()),
+            ("127.0.0.1".parse().unwrap(), "lo".to_string()),
+            (
+                "fe80::1c2a:4bff:fe3d:1".parse().unwrap(),
+                "eth0".to_string(),
+            ),
+            ("2001:db8::5".parse().unwrap(), "eth0".to_string()),
+            ("192.168.1.20".parse().unwrap(), "eth0".to_string()),
+            ("10.8.0.2".parse().unwrap(), "tun0".to_string()),
+        ])
+    }
+
+    fn ip(ip: &str) -> Option<IpAddr> {
+        Some(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn loopback_and_link_local_addresses_are_skipped() {
+        let addresses: Vec<String> = interfaces()
+            .iter()
+            .map(|address| format!("{} {}", address.interface, address.ip))
+            .collect();
+        assert_eq!(
+            addresses,
+            ["eth0 192.168.1.20", "tun0 10.8.0.2", "eth0 2001:db8::5"]
+        );
+    }
+
+    #[test]
+    fn the_default_route_is_preferred() {
+        let addresses = interfaces();
+        assert_eq!(select_ip(&addresses, None, ip("10.8.0.2")), ip("10.8.0.2"));
+        // a route through an address that isn't listed falls back to the first one
+        assert_eq!(
+            select_ip(&addresses, None, ip("172.16.0.9")),
+            ip("192.168.1.20")
+        );
+        assert_eq!(select_ip(&addresses, None, None), ip("192.168.1.20"));
+        assert_eq!(select_ip(&[], None, ip("10.8.0.2")), None);
+    }
+
+    #[test]
+    fn network_choice_wins() {
+        let addresses = interfaces();
+        let default_route = ip("192.168.1.20");
+        assert_eq!(
+            select_ip(&addresses, Some("tun0"), default_route),
+            ip("10.8.0.2")
+        );
+        assert_eq!(
+            select_ip(&addresses, Some("2001:db8::5"), default_route),
+            ip("2001:db8::5")
+        );
+        // an unknown choice is ignored
+        assert_eq!(
+            select_ip(&addresses, Some("wlan0"), default_route),
+            default_route
+        );
+        assert_eq!(host(&ip("2001:db8::5").unwrap()), "[2001:db8::5]");
+    }
+}
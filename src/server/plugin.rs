@@ -0,0 +1,188 @@
+use crate::{
+    plugin::{
+        routes::{PluginRequest, PluginResponse},
+        PluginManager,
+    },
+    CrateConfig,
+};
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::any,
+    Router,
+};
+
+/// Add the routes registered by plugins in `serve.routes`.
+///
+/// Routes overlapping a proxy are skipped, the proxy wins.
+pub fn add_plugin_routes(mut router: Router, config: &CrateConfig) -> Router {
+    let proxied: Vec<String> = config
+        .dioxus_config
+        .web
+        .proxy
+        .clone()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|proxy| proxy.backend.parse::<hyper::Uri>().ok())
+        .map(|url| url.path().trim_end_matches('/').to_string())
+        .collect();
+
+    for path in PluginManager::serve_routes() {
+        if proxied
+            .iter()
+            .any(|proxy| path == *proxy || path.starts_with(&format!("{proxy}/")))
+        {
+            log::warn!("The plugin route `{path}` is hidden by a proxy.");
+            continue;
+        }
+        let route = path.clone();
+        router = router.route(
+            &path,
+            any(move |req: Request<Body>| {
+                let route = route.clone();
+                async move { handle_route(route, req).await }
+            }),
+        );
+    }
+    router
+}
+
+/// Pass every text response through the `serve.middleware` of plugins, if any plugin has one.
+///
+/// Must be added after every route and the fallback, so it sees all of them.
+pub fn add_plugin_middleware(router: Router) -> Router {
+    if PluginManager::has_hook(|info| info.serve.middleware) {
+        router.layer(middleware::from_fn(transform_response))
+    } else {
+        router
+    }
+}
+
+async fn handle_route(route: String, req: Request<Body>) -> Response {
+    let (parts, body) = req.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    let request = PluginRequest {
+        method: parts.method.to_string(),
+        path: parts.uri.path().to_string(),
+        query: parts.uri.query().map(String::from),
+        headers: parts.headers,
+        body: body.to_vec(),
+    };
+
+    // plugins run Lua synchronously, so keep them off the async workers
+    let response =
+        tokio::task::spawn_blocking(move || PluginManager::handle_route(&route, &request)).await;
+    match response {
+        Ok(Some(response)) => into_response(response),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn transform_response(req: Request<Body>, next: Next<Body>) -> Response {
+    // the websockets of the CLI itself are left alone
+    if req.uri().path().starts_with("/_dioxus") {
+        return next.run(req).await;
+    }
+    let request = PluginRequest {
+        method: req.method().to_string(),
+        path: req.uri().path().to_string(),
+        query: req.uri().query().map(String::from),
+        headers: req.headers().clone(),
+        body: vec![],
+    };
+
+    let response = next.run(req).await;
+    // the whole body has to be buffered for the plugins, which isn't worth it for binary files
+    if !is_text(response.headers()) {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    let response = PluginResponse {
+        status: parts.status.as_u16(),
+        headers: parts.headers,
+        body: body.to_vec(),
+    };
+
+    let response =
+        tokio::task::spawn_blocking(move || PluginManager::transform_response(&request, response))
+            .await;
+    match response {
+        Ok(mut response) => {
+            // the length of the old body doesn't fit the new one
+            if response.body != body {
+                response.headers.remove(header::CONTENT_LENGTH);
+                response.headers.remove(header::TRANSFER_ENCODING);
+            }
+            into_response(response)
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// Whether the content type of a response is text, like html, css, js or json
+fn is_text(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    mime.starts_with("text/")
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime.as_str(),
+            "application/json" | "application/javascript" | "application/xml"
+        )
+}
+
+fn into_response(response: PluginResponse) -> Response {
+    let Ok(status) = StatusCode::from_u16(response.status) else {
+        let message = format!("A plugin returned the invalid status {}", response.status);
+        return (StatusCode::INTERNAL_SERVER_ERROR, message).into_response();
+    };
+    (status, response.headers, response.body).into_response()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn text_content_types() {
+        let content_type = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                header::CONTENT_TYPE,
+                header::HeaderValue::from_static(value),
+            );
+            is_text(&headers)
+        };
+        assert!(content_type("text/html; charset=utf-8"));
+        assert!(content_type("Text/CSS"));
+        assert!(content_type("application/json"));
+        assert!(content_type("application/manifest+json"));
+        assert!(content_type("image/svg+xml"));
+        assert!(content_type("application/javascript"));
+        assert!(!content_type("application/wasm"));
+        assert!(!content_type("image/png"));
+        assert!(!is_text(&HeaderMap::new()));
+    }
+}
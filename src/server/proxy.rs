@@ -81,6 +81,18 @@ pub fn add_proxy(mut router: Router, proxy: &WebProxyConfig) -> Result<Router> {
     Ok(router)
 }
 
+/// Forward every request that isn't matched by another route to `backend`.
+pub fn add_fallback_proxy(router: Router, backend: &str) -> Result<Router> {
+    let client = ProxyClient::new(backend.parse()?);
+
+    Ok(router.fallback(any(move |req| async move {
+        client
+            .send(req)
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))
+    })))
+}
+
 #[cfg(test)]
 mod test {
 
@@ -0,0 +1,396 @@
+//! Interactive terminal UI for `dioxus serve --interactive`
+//!
+//! The UI runs on its own thread and owns the terminal while it is active: log lines are
+//! redirected into it, and the rest of the CLI reports builds and app output with [`send`].
+//! Key presses are handed back to the caller as [`TuiAction`]s.
+use crate::{error::Result, logging};
+use cargo_metadata::diagnostic::Diagnostic;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::{Backend, CrosstermBackend},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, Paragraph},
+    Frame, Terminal,
+};
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{mpsc, Mutex, Once, PoisonError},
+    time::Duration,
+};
+
+/// How many lines each pane keeps
+const MAX_LINES: usize = 1000;
+
+lazy_static::lazy_static! {
+    static ref EVENTS: Mutex<Option<mpsc::Sender<TuiEvent>>> = Mutex::new(None);
+    static ref ANSI_ESCAPE: regex::Regex = regex::Regex::new("\x1b\\[[0-9;]*m").unwrap();
+}
+
+/// What the user asked for with a key binding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TuiAction {
+    Rebuild,
+    OpenBrowser,
+    ToggleHotReload,
+    Quit,
+}
+
+/// Something to show in the UI
+pub enum TuiEvent {
+    Log(log::Level, String),
+    /// A line written by the running app
+    AppLog(String),
+    BuildStarted,
+    BuildProgress(String),
+    BuildFinished {
+        changed: Vec<PathBuf>,
+        warnings: Vec<Diagnostic>,
+        elapsed_time: u128,
+    },
+    BuildFailed,
+    HotReload(bool),
+}
+
+/// Whether the UI owns the terminal right now
+pub fn is_active() -> bool {
+    EVENTS.lock().unwrap().is_some()
+}
+
+/// Show `event` in the UI, does nothing if it isn't active
+pub fn send(event: TuiEvent) {
+    if let Some(events) = EVENTS.lock().unwrap().as_ref() {
+        let _ = events.send(event);
+    }
+}
+
+/// Take over the terminal and start drawing the UI
+///
+/// `info` is shown in the header as `label: value` pairs. `hot_reload` is `None` if hot
+/// reloading isn't available, which disables its key binding.
+pub fn start(
+    info: Vec<(String, String)>,
+    hot_reload: Option<bool>,
+) -> Result<mpsc::Receiver<TuiAction>> {
+    let (event_tx, event_rx) = mpsc::channel();
+    let (action_tx, action_rx) = mpsc::channel();
+
+    install_panic_hook();
+    enable_raw_mode()?;
+    // from here on `stop` gives the terminal back, also if anything below fails
+    *EVENTS.lock().unwrap() = Some(event_tx.clone());
+    let guard = TerminalGuard;
+    execute!(std::io::stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+
+    let log_tx = event_tx;
+    logging::redirect_logs(Some(Box::new(move |level, message| {
+        let _ = log_tx.send(TuiEvent::Log(level, message));
+    })));
+
+    std::thread::spawn(move || {
+        let _guard = guard;
+        let mut state = State::new(info, hot_reload);
+        loop {
+            while let Ok(event) = event_rx.try_recv() {
+                state.update(event);
+            }
+            if terminal.draw(|frame| state.draw(frame)).is_err() {
+                break;
+            }
+
+            let Ok(true) = event::poll(Duration::from_millis(100)) else {
+                continue;
+            };
+            let Ok(Event::Key(key)) = event::read() else {
+                continue;
+            };
+            // some terminals report releases as well
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let action = match key.code {
+                KeyCode::Char('r') => TuiAction::Rebuild,
+                KeyCode::Char('o') => TuiAction::OpenBrowser,
+                KeyCode::Char('h') if state.hot_reload.is_some() => TuiAction::ToggleHotReload,
+                // raw mode swallows the ctrl-c signal
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    TuiAction::Quit
+                }
+                KeyCode::Char('c') => {
+                    state.clear();
+                    continue;
+                }
+                KeyCode::Char('q') | KeyCode::Esc => TuiAction::Quit,
+                _ => continue,
+            };
+            if action == TuiAction::Quit {
+                stop();
+            }
+            if action_tx.send(action).is_err() || action == TuiAction::Quit {
+                break;
+            }
+        }
+    });
+
+    Ok(action_rx)
+}
+
+/// Give the terminal back and print logs to stdout again
+pub fn stop() {
+    let events = EVENTS.lock().unwrap_or_else(PoisonError::into_inner).take();
+    logging::redirect_logs(None);
+    if events.is_some() {
+        restore_terminal();
+    }
+}
+
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(std::io::stdout(), LeaveAlternateScreen);
+}
+
+/// Calls [`stop`] when dropped, so the terminal is restored even if the UI thread panics
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        stop();
+    }
+}
+
+/// Leave raw mode and the alternate screen before a panic message is printed, otherwise
+/// it's lost and the shell is left unusable
+fn install_panic_hook() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            // `try_lock`, the panicking thread may hold the lock already
+            let active = EVENTS
+                .try_lock()
+                .map_or(true, |mut events| events.take().is_some());
+            if active {
+                restore_terminal();
+            }
+            previous(info);
+        }));
+    });
+}
+
+enum BuildStatus {
+    Building(String),
+    Finished { changed: usize, elapsed_time: u128 },
+    Failed,
+}
+
+struct State {
+    info: Vec<(String, String)>,
+    hot_reload: Option<bool>,
+    status: BuildStatus,
+    finished_at: String,
+    warnings: Vec<String>,
+    logs: VecDeque<(log::Level, String)>,
+    app_logs: VecDeque<String>,
+}
+
+impl State {
+    fn new(info: Vec<(String, String)>, hot_reload: Option<bool>) -> Self {
+        Self {
+            info,
+            hot_reload,
+            status: BuildStatus::Finished {
+                changed: 0,
+                elapsed_time: 0,
+            },
+            finished_at: chrono::Local::now().format("%H:%M:%S").to_string(),
+            warnings: vec![],
+            logs: VecDeque::new(),
+            app_logs: VecDeque::new(),
+        }
+    }
+
+    fn update(&mut self, event: TuiEvent) {
+        match event {
+            TuiEvent::Log(level, message) => {
+                let message = ANSI_ESCAPE.replace_all(&message, "").to_string();
+                for line in message.lines().filter(|line| !line.trim().is_empty()) {
+                    push_line(&mut self.logs, (level, line.to_string()));
+                }
+            }
+            TuiEvent::AppLog(line) => push_line(&mut self.app_logs, line),
+            TuiEvent::BuildStarted => self.status = BuildStatus::Building(String::new()),
+            TuiEvent::BuildProgress(progress) => self.status = BuildStatus::Building(progress),
+            TuiEvent::BuildFinished {
+                changed,
+                warnings,
+                elapsed_time,
+            } => {
+                self.status = BuildStatus::Finished {
+                    changed: changed.len(),
+                    elapsed_time,
+                };
+                self.finished_at = chrono::Local::now().format("%H:%M:%S").to_string();
+                self.warnings = warnings.iter().filter_map(format_warning).collect();
+            }
+            // the error itself is logged by whoever started the build
+            TuiEvent::BuildFailed => {
+                self.status = BuildStatus::Failed;
+                self.finished_at = chrono::Local::now().format("%H:%M:%S").to_string();
+            }
+            TuiEvent::HotReload(enabled) => self.hot_reload = Some(enabled),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.logs.clear();
+        self.app_logs.clear();
+        self.warnings.clear();
+    }
+
+    fn draw<B: Backend>(&self, frame: &mut Frame<B>) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                // borders, title, status, info and hot reload lines
+                Constraint::Length(self.info.len() as u16 + 5),
+                Constraint::Min(5),
+                Constraint::Length(8),
+                Constraint::Length(1),
+            ])
+            .split(frame.size());
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(rows[1]);
+
+        frame.render_widget(self.header(), rows[0]);
+
+        let logs = self.logs.iter().map(|(level, line)| {
+            let color = match level {
+                log::Level::Error => Color::Red,
+                log::Level::Warn => Color::Yellow,
+                _ => Color::Reset,
+            };
+            ListItem::new(Span::styled(line.clone(), Style::default().fg(color)))
+        });
+        frame.render_widget(tail_list("Logs", logs, panes[0]), panes[0]);
+
+        let app_logs = self.app_logs.iter().map(|line| ListItem::new(line.clone()));
+        frame.render_widget(tail_list("App", app_logs, panes[1]), panes[1]);
+
+        let warnings = self.warnings.iter().map(|line| {
+            ListItem::new(Span::styled(
+                line.clone(),
+                Style::default().fg(Color::Yellow),
+            ))
+        });
+        let title = format!("Warnings ({})", self.warnings.len());
+        frame.render_widget(tail_list(&title, warnings, rows[2]), rows[2]);
+
+        let mut keys = vec!["[r] rebuild", "[o] open browser", "[c] clear"];
+        if self.hot_reload.is_some() {
+            keys.push("[h] toggle hot reload");
+        }
+        keys.push("[q] quit");
+        let help = Paragraph::new(keys.join("  ")).style(Style::default().fg(Color::DarkGray));
+        frame.render_widget(help, rows[3]);
+    }
+
+    fn header(&self) -> Paragraph<'_> {
+        let title = Spans::from(vec![
+            Span::styled(
+                "Dioxus",
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(format!(" @ v{}", crate::DIOXUS_CLI_VERSION)),
+        ]);
+
+        let status = match &self.status {
+            BuildStatus::Building(progress) => Span::styled(
+                format!("Building... {progress}"),
+                Style::default().fg(Color::Cyan),
+            ),
+            BuildStatus::Finished {
+                changed,
+                elapsed_time,
+            } => {
+                let changed = match changed {
+                    0 => String::new(),
+                    n => format!(", {n} files changed"),
+                };
+                Span::styled(
+                    format!(
+                        "Ready in {elapsed_time} millis{changed} [{}]",
+                        self.finished_at
+                    ),
+                    Style::default().fg(Color::Green),
+                )
+            }
+            BuildStatus::Failed => Span::styled(
+                format!("Build failed [{}]", self.finished_at),
+                Style::default().fg(Color::Red),
+            ),
+        };
+
+        let mut lines = vec![title, Spans::from(status)];
+        for (label, value) in &self.info {
+            lines.push(Spans::from(vec![
+                Span::raw(format!("{label} : ")),
+                Span::styled(value.clone(), Style::default().fg(Color::Blue)),
+            ]));
+        }
+        if let Some(enabled) = self.hot_reload {
+            let value = if enabled { "RSX" } else { "Normal" };
+            lines.push(Spans::from(vec![
+                Span::raw("Hot Reload : "),
+                Span::styled(value, Style::default().fg(Color::Cyan)),
+            ]));
+        }
+
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL))
+    }
+}
+
+fn push_line<T>(lines: &mut VecDeque<T>, line: T) {
+    if lines.len() == MAX_LINES {
+        lines.pop_front();
+    }
+    lines.push_back(line);
+}
+
+/// A bordered list showing the last lines that fit into `area`
+fn tail_list<'a>(
+    title: &str,
+    items: impl DoubleEndedIterator<Item = ListItem<'a>>,
+    area: Rect,
+) -> List<'a> {
+    let visible = area.height.saturating_sub(2) as usize;
+    let mut items: Vec<ListItem> = items.rev().take(visible).collect();
+    items.reverse();
+    List::new(items).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(title.to_string()),
+    )
+}
+
+fn format_warning(warning: &Diagnostic) -> Option<String> {
+    // cargo ends with a summary like "2 warnings emitted"
+    if warning.spans.is_empty() {
+        return None;
+    }
+    let span = &warning.spans[0];
+    Some(format!(
+        "{}:{}: {}",
+        span.file_name, span.line_start, warning.message
+    ))
+}